@@ -6,27 +6,50 @@ use std::process::exit;
 use std::str::FromStr;
 
 use anyhow::{anyhow, Result};
-use ckb_capsule::checker::Checker;
-use ckb_capsule::config::{Contract, TemplateType};
+use ckb_capsule::cache::{list_volumes as list_cache_volumes, CargoCache};
+use ckb_capsule::checker::{print_json_report, print_report, Checker};
+use ckb_capsule::config::{CDependency, Contract, TemplateType};
 use ckb_capsule::config_manipulate::{append_contract, Document};
+use ckb_capsule::constants::Constants;
+use ckb_capsule::contract_deps::{build_contract, build_order};
+use ckb_capsule::contract_edit::{
+    create_skeleton_test, import_name, remove_contract, rename_contract,
+};
+use ckb_capsule::coverage::Coverage;
 use ckb_capsule::debugger;
+use ckb_capsule::debugger::dap::write_launch_config;
+use ckb_capsule::debugger::mock_tx::{parse_transaction, MockTxBuilder};
+use ckb_capsule::debugger::profile::profile_script;
+use ckb_capsule::debugger::syscall::{describe_syscalls, CellNames};
+use ckb_capsule::debugger::trace::{record_syscalls, record_trace, Replayer, Trace};
 use ckb_capsule::deployment::manage::{DeployOption, Manage as DeployManage};
+use ckb_capsule::deps::CDeps;
+use ckb_capsule::fuzzer::{FuzzConfig, Fuzzer};
 use ckb_capsule::generator::new_project;
+use ckb_capsule::generator::template::{
+    list_cached_templates, templates_cache_dir, ProjectTemplate,
+};
+use ckb_capsule::lint::{print_report as print_lint_report, Lint};
+use ckb_capsule::molecule::Molecule;
 use ckb_capsule::project_context::{
     read_config_file, write_config_file, BuildConfig, BuildEnv, Context, DeployEnv, CONFIG_FILE,
 };
 use ckb_capsule::recipe::get_recipe;
 use ckb_capsule::signal;
-use ckb_capsule::tester::Tester;
+use ckb_capsule::tester::{TestConfig, Tester};
+use ckb_capsule::upgrade::Upgrade;
+use ckb_capsule::util::cli::ask_for_confirm;
 use ckb_capsule::version::version_string;
 use ckb_capsule::wallet::cli_types::HumanCapacity;
 use ckb_capsule::wallet::{Address, Wallet, DEFAULT_CKB_CLI_BIN_NAME, DEFAULT_CKB_RPC_URL};
-use ckb_tool::ckb_types::core::Capacity;
+use ckb_tool::ckb_jsonrpc_types::OutPoint;
+use ckb_tool::ckb_types::{core::Capacity, H256};
 
 use clap::{App, AppSettings, Arg, SubCommand};
 
 const DEBUGGER_MAX_CYCLES: u64 = 70_000_000u64;
-const TEMPLATES_NAMES: &[&str] = &["rust", "c", "c-sharedlib"];
+const FUZZ_ITERATIONS: u64 = 1000u64;
+const TEMPLATES_NAMES: &[&str] = &["rust", "rust-sharedlib", "c", "c-sharedlib", "cpp"];
 
 fn append_contract_to_config(context: &Context, contract: &Contract) -> Result<()> {
     println!("Rewrite ckb_capsule.toml");
@@ -60,11 +83,33 @@ fn group_contracts_by_type(contracts: Vec<Contract>) -> HashMap<TemplateType, Ve
     contracts_by_type
 }
 
+/// parse out point from '<tx_hash>:<index>'
+fn parse_out_point(s: &str) -> Result<OutPoint> {
+    let mut parts = s.split(':');
+    let (tx_hash, index) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(tx_hash), Some(index), None) => (tx_hash, index),
+        _ => {
+            return Err(anyhow!(
+                "invalid out point '{}', expect '<tx_hash>:<index>'",
+                s
+            ))
+        }
+    };
+    let tx_hash = H256::from_str(tx_hash.trim_start_matches("0x"))
+        .map_err(|err| anyhow!("invalid tx hash: {}", err))?;
+    let index: u32 = index.parse()?;
+    Ok(OutPoint {
+        tx_hash,
+        index: index.into(),
+    })
+}
+
 fn run_cli() -> Result<()> {
     env_logger::init();
 
     let version = version_string();
     let default_max_cycles_str = format!("{}", DEBUGGER_MAX_CYCLES);
+    let default_fuzz_iterations_str = format!("{}", FUZZ_ITERATIONS);
 
     let contract_args = [
         Arg::with_name("name")
@@ -80,23 +125,80 @@ fn run_cli() -> Result<()> {
             .takes_value(true),
     ];
 
+    let script_group_args = [
+        Arg::with_name("template-file")
+            .long("template-file")
+            .short("f")
+            .help("Transaction debugging template file")
+            .required(true)
+            .takes_value(true),
+        Arg::with_name("name")
+            .short("n")
+            .long("name")
+            .required(true)
+            .takes_value(true)
+            .help("contract name"),
+        Arg::with_name("release")
+            .long("release")
+            .help("Debugging release contract"),
+        Arg::with_name("script-group-type")
+            .long("script-group-type")
+            .possible_values(&["type", "lock"])
+            .help("Script type")
+            .required(true)
+            .takes_value(true),
+        Arg::with_name("cell-index")
+            .long("cell-index")
+            .required(true)
+            .help("index of the cell")
+            .takes_value(true),
+        Arg::with_name("cell-type")
+            .long("cell-type")
+            .required(true)
+            .possible_values(&["input", "output"])
+            .help("cell type")
+            .takes_value(true),
+        Arg::with_name("max-cycles")
+            .long("max-cycles")
+            .default_value(&default_max_cycles_str)
+            .help("Max cycles")
+            .takes_value(true),
+    ];
+
     let mut app = App::new("Capsule")
         .setting(AppSettings::ArgRequiredElseHelp)
         .version(version.as_str())
         .author("Nervos Developer Tools Team")
         .about("Capsule CKB contract scaffold")
-        .subcommand(SubCommand::with_name("check").about("Check environment and dependencies").display_order(0))
-        .subcommand(SubCommand::with_name("new").about("Create a new project").args(&contract_args).display_order(1))
+        .subcommand(SubCommand::with_name("check").about("Check environment and dependencies").args(&[
+                    Arg::with_name("format").long("format").help("Output format").possible_values(&["table", "json"]).default_value("table").takes_value(true),
+                    Arg::with_name("api").long("api").help("CKB RPC url").default_value(DEFAULT_CKB_RPC_URL).takes_value(true),
+        ]).display_order(0))
+        .subcommand(SubCommand::with_name("new").about("Create a new project").args(&contract_args).args(&[
+                    Arg::with_name("template-path").long("template-path").help("Project template directory with a capsule-template.toml").conflicts_with("template-git").takes_value(true),
+                    Arg::with_name("template-git").long("template-git").help("Project template git repository, e.g. https://github.com/org/skeleton.git#v1").takes_value(true),
+                    Arg::with_name("var").long("var").help("Project template variable, e.g. --var author=alice").multiple(true).number_of_values(1).takes_value(true),
+                    Arg::with_name("yes").long("yes").short("y").help("Run post create hooks of the template without confirmation"),
+        ]).display_order(1))
         .subcommand(SubCommand::with_name("new-contract").about("Create a new contract").args(&contract_args).display_order(2))
         .subcommand(SubCommand::with_name("build").about("Build contracts").arg(Arg::with_name("name").short("n").long("name").multiple(true).takes_value(true).help("contract name")).arg(
                     Arg::with_name("release").long("release").help("Build contracts in release mode.")
         ).arg(Arg::with_name("debug-output").long("debug-output").help("Always enable debugging output")).display_order(3))
+        .subcommand(SubCommand::with_name("lint").about("Run static checks of contracts").arg(Arg::with_name("name").short("n").long("name").multiple(true).takes_value(true).help("contract name")).display_order(3))
         .subcommand(SubCommand::with_name("run").about("Run command in contract build image").usage("ckb_capsule run --name <name> 'echo list contract dir: && ls'")
         .args(&[Arg::with_name("name").short("n").long("name").required(true).takes_value(true).help("contract name"),
                 Arg::with_name("cmd").required(true).multiple(true).help("command to run")])
         .display_order(4))
         .subcommand(SubCommand::with_name("test").about("Run tests").arg(
                     Arg::with_name("release").long("release").help("Test release mode contracts.")
+        ).arg(
+                    Arg::with_name("no-build").long("no-build").help("Fail instead of rebuilding outdated contracts.")
+        ).arg(
+                    Arg::with_name("coverage").long("coverage").conflicts_with("release").help("Collect code coverage of contracts, reports are written to build/coverage.")
+        ).arg(
+                    Arg::with_name("html").long("html").requires("coverage").help("Generate HTML coverage reports with genhtml.")
+        ).arg(
+                    Arg::with_name("min-coverage").long("min-coverage").requires("coverage").takes_value(true).help("Fail if line coverage of any contract is below the percentage.")
         ).display_order(5))
         .subcommand(
             SubCommand::with_name("deploy")
@@ -123,6 +225,100 @@ fn run_cli() -> Result<()> {
         )
         .subcommand(SubCommand::with_name("clean").about("Remove contracts targets and binaries").arg(Arg::with_name("name").short("n").long("name").multiple(true).takes_value(true).help("contract name"))
         .display_order(7))
+        .subcommand(
+            SubCommand::with_name("fuzz")
+                .about("Fuzz a contract with random transactions")
+                .args(&[
+                    Arg::with_name("name").help("contract name").index(1).required(true).takes_value(true),
+                    Arg::with_name("iterations").long("iterations").help("Number of transactions to run")
+                        .default_value(&default_fuzz_iterations_str).takes_value(true),
+                    Arg::with_name("seed").long("seed").help("Random seed, use it to reproduce a previous run").takes_value(true),
+                    Arg::with_name("release").long("release").help("Fuzz release mode contract."),
+                ]).display_order(9),
+        )
+        .subcommand(
+            SubCommand::with_name("deps")
+            .about("Manage C dependencies")
+            .subcommand(SubCommand::with_name("fetch").about("Fetch missing or modified dependencies")
+                .arg(Arg::with_name("name").short("n").long("name").multiple(true).takes_value(true).help("dependency name")))
+            .subcommand(SubCommand::with_name("update").about("Update git dependencies to the latest commit")
+                .arg(Arg::with_name("name").short("n").long("name").multiple(true).takes_value(true).help("dependency name")))
+            .subcommand(SubCommand::with_name("vendor").about("Fetch dependencies and commit them into the project")
+                .arg(Arg::with_name("name").short("n").long("name").multiple(true).takes_value(true).help("dependency name")))
+            .subcommand(SubCommand::with_name("add").about("Add a dependency, e.g. secp256k1")
+                .args(&[
+                    Arg::with_name("name").help("dependency name").index(1).required(true).takes_value(true),
+                    Arg::with_name("source").long("source").help("Git repository url or tarball url (.tar.gz)").required(true).takes_value(true),
+                    Arg::with_name("revision").long("revision").help("Git commit, default is the remote HEAD").takes_value(true),
+                ]))
+            .display_order(10),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+            .about("Import an existing contract crate or C sources")
+            .args(&[
+                Arg::with_name("path").help("path of the crate, C source file or dir").index(1).required(true).takes_value(true),
+                Arg::with_name("template").long("template").help("language template").possible_values(TEMPLATES_NAMES).default_value(TEMPLATES_NAMES[0]).takes_value(true),
+                Arg::with_name("name").long("name").short("n").help("contract name, default is the package name or the file name").takes_value(true),
+                Arg::with_name("link").long("link").help("Link the sources instead of copying, sources must be under the project dir, or `contracts/c` for C contracts"),
+                Arg::with_name("no-build").long("no-build").help("Don't check that the contract builds for the CKB target"),
+                Arg::with_name("with-test").long("with-test").help("Generate a skeleton test into the tests crate"),
+            ])
+            .display_order(16),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+            .about("Manage the cargo cache of the build image")
+            .subcommand(SubCommand::with_name("info").about("Show the cargo cache of the project and cache volumes"))
+            .subcommand(SubCommand::with_name("prune").about("Remove extracted sources and git checkouts, downloaded crates are kept"))
+            .subcommand(SubCommand::with_name("clean").about("Remove the cargo cache of the project")
+                .args(&[
+                    Arg::with_name("all").long("all").help("Remove all cache volumes of capsule"),
+                    Arg::with_name("yes").long("yes").short("y").help("Remove without confirmation"),
+                ]))
+            .display_order(17),
+        )
+        .subcommand(
+            SubCommand::with_name("rename-contract")
+            .about("Rename a contract")
+            .args(&[
+                Arg::with_name("name").help("contract name").index(1).required(true).takes_value(true),
+                Arg::with_name("new-name").help("new contract name").index(2).required(true).takes_value(true),
+                Arg::with_name("yes").long("yes").short("y").help("Apply changes without confirmation"),
+            ])
+            .display_order(14),
+        )
+        .subcommand(
+            SubCommand::with_name("remove-contract")
+            .about("Remove a contract")
+            .args(&[
+                Arg::with_name("name").help("contract name").index(1).required(true).takes_value(true),
+                Arg::with_name("yes").long("yes").short("y").help("Apply changes without confirmation"),
+            ])
+            .display_order(15),
+        )
+        .subcommand(
+            SubCommand::with_name("upgrade-project")
+            .about("Upgrade the project to the current capsule version")
+            .args(&[
+                Arg::with_name("dry-run").long("dry-run").help("Show changes without writing files"),
+                Arg::with_name("yes").long("yes").short("y").help("Apply changes without confirmation"),
+            ])
+            .display_order(13),
+        )
+        .subcommand(
+            SubCommand::with_name("template")
+            .about("Project templates")
+            .subcommand(SubCommand::with_name("list").about("List built-in and cached project templates"))
+            .display_order(12),
+        )
+        .subcommand(
+            SubCommand::with_name("mol")
+            .about("Molecule schemas")
+            .subcommand(SubCommand::with_name("check").about("Check schemas listed in capsule.toml"))
+            .subcommand(SubCommand::with_name("gen").about("Generate code from schemas, it's done by build automatically"))
+            .display_order(11),
+        )
         .subcommand(
             SubCommand::with_name("debugger")
             .about("CKB debugger")
@@ -132,58 +328,98 @@ fn run_cli() -> Result<()> {
                 .args(&[
                     Arg::with_name("name").long("name").short("n").help(
                         "contract name",
-                    ).required(true).takes_value(true),
+                    ).required_unless_one(&["tx-hash", "tx-file"]).takes_value(true),
                     Arg::with_name("output-file")
                         .long("output-file")
                         .short("o")
                         .help("Output file path").required(true).takes_value(true),
+                    Arg::with_name("tx-hash")
+                        .long("tx-hash")
+                        .help("Generate from an on-chain transaction").conflicts_with("tx-file").takes_value(true),
+                    Arg::with_name("tx-file")
+                        .long("tx-file")
+                        .help("Generate from a transaction json file, e.g. a failed send_transaction request").takes_value(true),
+                    Arg::with_name("replace-cell")
+                        .long("replace-cell")
+                        .help("Replace data of the cell '<tx_hash>:<index>' with the contract").requires("name").takes_value(true),
+                    Arg::with_name("api")
+                        .long("api")
+                        .help("CKB RPC url").default_value(DEFAULT_CKB_RPC_URL).takes_value(true),
                 ])
             )
             .subcommand(
                 SubCommand::with_name("start")
                 .about("Start GDB")
+                .args(&script_group_args)
                 .args(&[
-                    Arg::with_name("template-file")
-                        .long("template-file")
-                        .short("f")
-                        .help("Transaction debugging template file")
-                        .required(true)
-                        .takes_value(true),
-                    Arg::with_name("name")
-                        .short("n")
-                        .long("name")
-                        .required(true)
-                        .takes_value(true)
-                        .help("contract name"),
-                    Arg::with_name("release").long("release").help("Debugging release contract"),
-                    Arg::with_name("script-group-type")
-                        .long("script-group-type")
-                        .possible_values(&["type", "lock"])
-                        .help("Script type")
-                        .required(true)
-                        .takes_value(true),
-                    Arg::with_name("cell-index")
-                        .long("cell-index")
-                        .required(true)
-                        .help("index of the cell")
-                        .takes_value(true),
-                    Arg::with_name("cell-type")
-                        .long("cell-type")
-                        .required(true)
-                        .possible_values(&["input", "output"])
-                        .help("cell type")
-                        .takes_value(true),
-                    Arg::with_name("max-cycles")
-                        .long("max-cycles")
-                        .default_value(&default_max_cycles_str)
-                        .help("Max cycles")
-                        .takes_value(true),
                     Arg::with_name("listen")
                         .long("listen")
                         .short("l")
                         .help("GDB server listening port")
                         .default_value("8000").required(true).takes_value(true),
                     Arg::with_name("only-server").long("only-server").help("Only start debugger server"),
+                    Arg::with_name("dap").long("dap").help("Only start debugger server and generate .vscode/launch.json for editors"),
+                ])
+            )
+            .subcommand(
+                SubCommand::with_name("run")
+                .about("Run script group without GDB")
+                .args(&script_group_args)
+                .args(&[
+                    Arg::with_name("trace-syscalls").long("trace-syscalls").help("Print ckb_load_* syscalls, the script is re-run by single stepping under GDB, which is slow for scripts of millions of cycles"),
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .short("l")
+                        .help("GDB server listening port used by --trace-syscalls")
+                        .default_value("8000").takes_value(true),
+                ])
+            )
+            .subcommand(
+                SubCommand::with_name("profile")
+                .about("Profile cycles of script group")
+                .args(&script_group_args)
+                .args(&[
+                    Arg::with_name("flamegraph").long("flamegraph").help("Generate SVG flamegraph with inferno-flamegraph"),
+                    Arg::with_name("limit").long("limit").help("Number of functions to display").default_value("20").takes_value(true),
+                ])
+            )
+            .subcommand(
+                SubCommand::with_name("record")
+                .about("Record execution trace of script group, the script is single stepped under GDB which takes minutes to hours for scripts of millions of cycles")
+                .args(&script_group_args)
+                .args(&[
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .short("l")
+                        .help("GDB server listening port")
+                        .default_value("8000").takes_value(true),
+                ])
+            )
+            .subcommand(
+                SubCommand::with_name("replay")
+                .about("Replay a recorded execution trace")
+                .args(&[
+                    Arg::with_name("trace-file")
+                        .long("trace-file")
+                        .short("f")
+                        .help("Trace file")
+                        .required(true)
+                        .takes_value(true),
+                    Arg::with_name("name").long("name").short("n").help("contract name").required(true).takes_value(true),
+                    Arg::with_name("release").long("release").help("Replay with release contract"),
+                ])
+            )
+            .subcommand(
+                SubCommand::with_name("check-template")
+                .about("Report unresolved marks of transaction debugging template")
+                .args(&[
+                    Arg::with_name("template-file")
+                        .long("template-file")
+                        .short("f")
+                        .help("Transaction debugging template file")
+                        .required(true)
+                        .takes_value(true),
+                    Arg::with_name("release").long("release").help("Check with release contracts"),
                 ])
             )
                 .display_order(8),
@@ -199,8 +435,18 @@ fn run_cli() -> Result<()> {
 
     let matches = app.get_matches();
     match matches.subcommand() {
-        ("check", _args) => {
-            Checker::build()?.print_report();
+        ("check", Some(args)) => {
+            let checks = Checker::build()?.run_checks(args.value_of("api").expect("api"));
+            let failed = match args.value_of("format") {
+                Some("json") => print_json_report(&checks)?,
+                _ => print_report(&checks),
+            };
+            if !failed.is_empty() {
+                return Err(anyhow!(
+                    "missing required dependencies: {}",
+                    failed.join(", ")
+                ));
+            }
         }
         ("new", Some(args)) => {
             let mut name = args
@@ -218,14 +464,64 @@ fn run_cli() -> Result<()> {
             } else {
                 path.push(env::current_dir()?);
             }
+            let project_template = if let Some(dir) = args.value_of("template-path") {
+                Some(ProjectTemplate::load(dir)?)
+            } else if let Some(source) = args.value_of("template-git") {
+                Some(ProjectTemplate::from_git(source)?)
+            } else {
+                None
+            };
+            let mut vars = HashMap::new();
+            for var in args.values_of("var").into_iter().flatten() {
+                let mut parts = var.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) => {
+                        vars.insert(key.trim().to_string(), value.to_string());
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "invalid template variable '{}', expect key=value",
+                            var
+                        ))
+                    }
+                }
+            }
+            vars.insert("name".to_string(), name.clone());
+            vars.insert(
+                "path".to_string(),
+                path.join(&name).to_str().expect("path").to_string(),
+            );
+            vars.insert("version".to_string(), version_string());
+            // report invalid templates before the project is created
+            if let Some(project_template) = project_template.as_ref() {
+                project_template.check(&vars)?;
+            }
+            // hooks are arbitrary shell commands, confirm them before the project is created
+            let run_hooks = match project_template.as_ref() {
+                Some(project_template) if !project_template.manifest.post_create.is_empty() => {
+                    println!("Post create hooks of the template:");
+                    for hook in &project_template.manifest.post_create {
+                        println!("  {}", hook);
+                    }
+                    args.is_present("yes") || ask_for_confirm("Run the hooks?")?
+                }
+                _ => false,
+            };
             let project_path = new_project(name.to_string(), path, &signal)?;
             let context = Context::load_from_path(&project_path)?;
             let c = Contract {
                 name,
                 template_type,
+                cflags: None,
+                ldflags: None,
+                dependencies: Vec::new(),
             };
             get_recipe(context.clone(), c.template_type)?.create_contract(&c, true, &signal)?;
             append_contract_to_config(&context, &c)?;
+            if let Some(project_template) = project_template {
+                println!("Apply template {}", project_template.manifest.name);
+                project_template.apply(&project_path, &vars, run_hooks, &signal)?;
+            }
             println!("Done");
         }
         ("new-contract", Some(args)) => {
@@ -236,6 +532,9 @@ fn run_cli() -> Result<()> {
             let contract = Contract {
                 name,
                 template_type,
+                cflags: None,
+                ldflags: None,
+                dependencies: Vec::new(),
             };
             let recipe = get_recipe(context.clone(), contract.template_type)?;
             if recipe.exists(&contract.name) {
@@ -245,6 +544,58 @@ fn run_cli() -> Result<()> {
             append_contract_to_config(&context, &contract)?;
             println!("Done");
         }
+        ("import", Some(args)) => {
+            let context = Context::load()?;
+            let source = PathBuf::from(args.value_of("path").expect("path"));
+            if !source.exists() {
+                return Err(anyhow!("can't find {:?}", source));
+            }
+            let template_type: TemplateType =
+                args.value_of("template").expect("template").parse()?;
+            let name = match args.value_of("name") {
+                Some(name) => name.trim().to_string(),
+                None => import_name(&source, template_type)?,
+            };
+            let contract = Contract {
+                name,
+                template_type,
+                cflags: None,
+                ldflags: None,
+                dependencies: Vec::new(),
+            };
+            let recipe = get_recipe(context.clone(), contract.template_type)?;
+            if recipe.exists(&contract.name)
+                || context
+                    .config
+                    .contracts
+                    .iter()
+                    .any(|c| c.name == contract.name)
+            {
+                return Err(anyhow!("contract '{}' is already exists", contract.name));
+            }
+            recipe.import_contract(&contract, &source, args.is_present("link"), &signal)?;
+            append_contract_to_config(&context, &contract)?;
+            if !args.is_present("no-build") {
+                println!("Building contract {}", contract.name);
+                let build_config = BuildConfig {
+                    build_env: BuildEnv::Debug,
+                    always_debug: false,
+                };
+                if let Err(err) = recipe.run_build(&contract, build_config, &signal) {
+                    return Err(anyhow!(
+                        "imported contract '{}' can't be built for the CKB target: {}\nhint: fix the sources and run `capsule build -n {}`, or remove it by `capsule remove-contract {}`",
+                        contract.name,
+                        err,
+                        contract.name,
+                        contract.name
+                    ));
+                }
+            }
+            if args.is_present("with-test") {
+                create_skeleton_test(&context, &contract)?;
+            }
+            println!("Done");
+        }
         ("build", Some(args)) => {
             let context = Context::load()?;
             let build_names: Vec<&str> = args
@@ -262,18 +613,37 @@ fn run_cli() -> Result<()> {
                 always_debug,
             };
 
-            let contracts: Vec<_> = select_contracts(&context, &build_names);
+            let contracts = build_order(&context.config.contracts, &build_names)?;
             if contracts.is_empty() {
                 println!("Nothing to do");
             } else {
+                Molecule::new(&context).generate(&signal)?;
                 for contract in contracts {
                     println!("Building contract {}", contract.name);
-                    let recipe = get_recipe(context.clone(), contract.template_type)?;
-                    recipe.run_build(&contract, build_config, &signal)?;
+                    build_contract(&context, &contract, build_config, &signal)?;
                 }
+                Constants::new(&context).generate()?;
                 println!("Done");
             }
         }
+        ("lint", Some(args)) => {
+            let context = Context::load()?;
+            let names: Vec<&str> = args
+                .values_of("name")
+                .map(|values| values.collect())
+                .unwrap_or_default();
+            let contracts: Vec<_> = select_contracts(&context, &names);
+            if contracts.is_empty() {
+                println!("Nothing to do");
+            } else {
+                Molecule::new(&context).generate(&signal)?;
+                let findings = Lint::new(&context).run(&contracts, &signal)?;
+                let errors = print_lint_report(&findings);
+                if errors > 0 {
+                    return Err(anyhow!("lint failed with {} errors", errors));
+                }
+            }
+        }
         ("clean", Some(args)) => {
             let context = Context::load()?;
             let build_names: Vec<&str> = args
@@ -312,7 +682,74 @@ fn run_cli() -> Result<()> {
             } else {
                 BuildEnv::Debug
             };
-            Tester::run(&context, build_env, &signal)?;
+            let coverage = args.is_present("coverage");
+            let test_config = TestConfig {
+                build_env,
+                rebuild: !args.is_present("no-build"),
+                coverage,
+            };
+            Tester::run(&context, test_config, &signal)?;
+            if coverage {
+                let min_coverage: Option<f64> = match args.value_of("min-coverage") {
+                    Some(value) => Some(value.parse()?),
+                    None => None,
+                };
+                let coverage = Coverage::new(&context);
+                let summaries = coverage.generate_reports(&signal)?;
+                if args.is_present("html") {
+                    coverage.generate_html(&summaries, &signal)?;
+                }
+                println!("------------------------------");
+                for summary in &summaries {
+                    println!(
+                        "{}\t{:.2}% ({}/{} lines)\t{:?}",
+                        summary.name,
+                        summary.percent(),
+                        summary.lines_hit,
+                        summary.lines_found,
+                        summary.report_path
+                    );
+                }
+                println!("------------------------------");
+                if let Some(min_coverage) = min_coverage {
+                    let failed: Vec<_> = summaries
+                        .iter()
+                        .filter(|summary| summary.percent() < min_coverage)
+                        .map(|summary| summary.name.as_str())
+                        .collect();
+                    if !failed.is_empty() {
+                        return Err(anyhow!(
+                            "coverage of contracts {} is below {}%",
+                            failed.join(", "),
+                            min_coverage
+                        ));
+                    }
+                }
+            }
+        }
+        ("fuzz", Some(args)) => {
+            let context = Context::load()?;
+            let name = args.value_of("name").expect("name");
+            let contract = match context.config.contracts.iter().find(|c| name == c.name) {
+                Some(c) => c.clone(),
+                None => return Err(anyhow!("can't find contract '{}'", name)),
+            };
+            let build_env: BuildEnv = if args.is_present("release") {
+                BuildEnv::Release
+            } else {
+                BuildEnv::Debug
+            };
+            let iterations: u64 = args.value_of("iterations").expect("iterations").parse()?;
+            let seed: Option<u64> = match args.value_of("seed") {
+                Some(seed) => Some(seed.parse()?),
+                None => None,
+            };
+            let config = FuzzConfig {
+                build_env,
+                iterations,
+                seed,
+            };
+            Fuzzer::new(&context).run(&contract, config, &signal)?;
         }
         ("deploy", Some(args)) => {
             Checker::build()?.check_ckb_cli()?;
@@ -337,11 +774,217 @@ fn run_cli() -> Result<()> {
             };
             let opt = DeployOption { migrate, tx_fee };
             DeployManage::new(migration_dir, context.load_deployment()?).deploy(wallet, opt)?;
+            Constants::new(&context).generate()?;
+        }
+        ("deps", Some(sub_matches)) => {
+            let context = Context::load()?;
+            let deps = CDeps::new(&context);
+            match sub_matches.subcommand() {
+                ("add", Some(args)) => {
+                    let dep = CDependency {
+                        name: args.value_of("name").expect("name").to_string(),
+                        source: args.value_of("source").expect("source").to_string(),
+                        revision: args.value_of("revision").map(|r| r.to_string()),
+                        checksum: None,
+                        vendored: false,
+                    };
+                    deps.add(dep, &signal)?;
+                }
+                (command, Some(args)) => {
+                    let names: Vec<&str> = args
+                        .values_of("name")
+                        .map(|values| values.collect())
+                        .unwrap_or_default();
+                    match command {
+                        "fetch" => deps.fetch(&names, &signal)?,
+                        "update" => deps.update(&names, &signal)?,
+                        "vendor" => deps.vendor(&names, &signal)?,
+                        _ => unreachable!(),
+                    }
+                }
+                (command, None) => {
+                    eprintln!("unknown deps subcommand '{}'", command);
+                    eprintln!("{}", help_str);
+                    exit(1);
+                }
+            }
+            println!("Done");
+        }
+        ("rename-contract", Some(args)) | ("remove-contract", Some(args)) => {
+            let context = Context::load()?;
+            let name = args.value_of("name").expect("name").trim();
+            let changes = match args.value_of("new-name") {
+                Some(new_name) => rename_contract(&context, name, new_name.trim())?,
+                None => remove_contract(&context, name)?,
+            };
+            changes.preview(&signal)?;
+            if !args.is_present("yes") && !ask_for_confirm("Apply changes?")? {
+                println!("Cancelled");
+                return Ok(());
+            }
+            changes.apply()?;
+            println!("Done");
+        }
+        ("upgrade-project", Some(args)) => {
+            let mut upgrade = Upgrade::new(env::current_dir()?);
+            let project_version = upgrade.project_version()?;
+            let applied = upgrade.migrate()?;
+            if upgrade.is_empty() {
+                println!("Project is up to date");
+                return Ok(());
+            }
+            println!(
+                "Upgrade project from {} to {}",
+                project_version,
+                version_string()
+            );
+            for migration in applied {
+                println!("* {}", migration.description);
+            }
+            upgrade.diff(&signal)?;
+            if args.is_present("dry-run") {
+                return Ok(());
+            }
+            if !args.is_present("yes") && !ask_for_confirm("Apply changes?")? {
+                println!("Cancelled");
+                return Ok(());
+            }
+            upgrade.apply()?;
+            println!("Done");
+        }
+        ("template", Some(sub_matches)) => match sub_matches.subcommand() {
+            ("list", _args) => {
+                println!("Built-in templates:");
+                for name in TEMPLATES_NAMES {
+                    println!("  {}", name);
+                }
+                println!("Cached templates ({:?}):", templates_cache_dir()?);
+                for template in list_cached_templates()? {
+                    println!(
+                        "  {}\t{}\t{:?}",
+                        template.manifest.name, template.manifest.description, template.dir
+                    );
+                }
+            }
+            (command, _) => {
+                eprintln!("unknown template subcommand '{}'", command);
+                eprintln!("{}", help_str);
+                exit(1);
+            }
+        },
+        ("cache", Some(sub_matches)) => {
+            // the cache of the project if the current dir is a project
+            let cache = match Context::load() {
+                Ok(context) => CargoCache::for_project(&context),
+                Err(_) => CargoCache::shared(),
+            };
+            match sub_matches.subcommand() {
+                ("info", _args) => {
+                    cache.info(&signal)?;
+                    println!("Cache volumes:");
+                    for volume in list_cache_volumes()? {
+                        println!("  {}", volume);
+                    }
+                }
+                ("prune", _args) => {
+                    cache.prune(&signal)?;
+                    println!("Done");
+                }
+                ("clean", Some(args)) => {
+                    let volumes = list_cache_volumes()?;
+                    let mut caches = Vec::new();
+                    match cache {
+                        CargoCache::Volume(ref name) if !volumes.contains(name) => {}
+                        cache => caches.push(cache),
+                    }
+                    if args.is_present("all") {
+                        for volume in volumes {
+                            let volume = CargoCache::Volume(volume);
+                            if !caches.contains(&volume) {
+                                caches.push(volume);
+                            }
+                        }
+                    }
+                    if caches.is_empty() {
+                        println!("Nothing to do");
+                        return Ok(());
+                    }
+                    for cache in &caches {
+                        println!("{}", cache);
+                    }
+                    if !args.is_present("yes") && !ask_for_confirm("Remove cargo caches?")? {
+                        println!("Cancelled");
+                        return Ok(());
+                    }
+                    for cache in caches {
+                        cache.clean(&signal)?;
+                    }
+                    println!("Done");
+                }
+                (command, _) => {
+                    eprintln!("unknown cache subcommand '{}'", command);
+                    eprintln!("{}", help_str);
+                    exit(1);
+                }
+            }
+        }
+        ("mol", Some(sub_matches)) => {
+            let context = Context::load()?;
+            let molecule = Molecule::new(&context);
+            match sub_matches.subcommand() {
+                ("check", _args) => {
+                    let errors = molecule.check(&signal)?;
+                    if !errors.is_empty() {
+                        for (schema, err) in errors {
+                            eprintln!("{:?}: {}", schema, err);
+                        }
+                        exit(1);
+                    }
+                    println!(
+                        "{} schemas are valid",
+                        context.config.molecule.schemas.len()
+                    );
+                }
+                ("gen", _args) => {
+                    molecule.generate(&signal)?;
+                    println!("Done");
+                }
+                (command, _) => {
+                    eprintln!("unknown mol subcommand '{}'", command);
+                    eprintln!("{}", help_str);
+                    exit(1);
+                }
+            }
         }
         ("debugger", Some(sub_matches)) => match sub_matches.subcommand() {
             ("gen-template", Some(args)) => {
-                let contract = args.value_of("name").expect("contract name");
-                let template_content = debugger::build_template(contract.to_string())?;
+                let tx = if let Some(tx_hash) = args.value_of("tx-hash") {
+                    let builder = MockTxBuilder::new(args.value_of("api").expect("api"));
+                    let tx_hash = H256::from_str(tx_hash.trim_start_matches("0x"))
+                        .map_err(|err| anyhow!("invalid tx hash: {}", err))?;
+                    Some((builder.load_transaction(tx_hash)?, builder))
+                } else if let Some(tx_file) = args.value_of("tx-file") {
+                    let builder = MockTxBuilder::new(args.value_of("api").expect("api"));
+                    Some((parse_transaction(&fs::read_to_string(tx_file)?)?, builder))
+                } else {
+                    None
+                };
+                let template_content = match tx {
+                    Some((tx, builder)) => {
+                        let replace = match args.value_of("replace-cell") {
+                            Some(out_point) => {
+                                let contract = args.value_of("name").expect("contract name");
+                                Some((parse_out_point(out_point)?, contract.to_string()))
+                            }
+                            None => None,
+                        };
+                        builder.build_template(tx, replace)?
+                    }
+                    None => {
+                        let contract = args.value_of("name").expect("contract name");
+                        debugger::build_template(contract.to_string())?
+                    }
+                };
                 let template_path = args.value_of("output-file").expect("output file");
                 fs::write(&template_path, template_content)?;
                 println!("Write transaction debugging template to {}", template_path,);
@@ -364,7 +1007,22 @@ fn run_cli() -> Result<()> {
                     .unwrap()
                     .parse()
                     .expect("listen port");
-                let tty = !args.is_present("only-server");
+                let dap = args.is_present("dap");
+                if dap {
+                    let contract = match context
+                        .config
+                        .contracts
+                        .iter()
+                        .find(|c| contract_name == c.name)
+                    {
+                        Some(c) => c.clone(),
+                        None => return Err(anyhow!("can't find contract '{}'", contract_name)),
+                    };
+                    let launch_path =
+                        write_launch_config(&context, &contract, build_env, listen_port)?;
+                    println!("Write debugging configuration to {:?}", launch_path);
+                }
+                let tty = !args.is_present("only-server") && !dap;
                 debugger::start_debugger(
                     &context,
                     template_path,
@@ -379,6 +1037,174 @@ fn run_cli() -> Result<()> {
                     &signal,
                 )?;
             }
+            ("run", Some(args)) => {
+                let context = Context::load()?;
+                let template_path = args.value_of("template-file").expect("template file");
+                let build_env: BuildEnv = if args.is_present("release") {
+                    BuildEnv::Release
+                } else {
+                    BuildEnv::Debug
+                };
+                let contract_name = args.value_of("name").expect("contract name");
+                let script_group_type = args.value_of("script-group-type").unwrap();
+                let cell_index: usize = args.value_of("cell-index").unwrap().parse()?;
+                let cell_type = args.value_of("cell-type").unwrap();
+                let max_cycles: u64 = args.value_of("max-cycles").unwrap().parse()?;
+                let result = debugger::run_script(
+                    &context,
+                    template_path,
+                    contract_name,
+                    build_env,
+                    script_group_type,
+                    cell_index,
+                    cell_type,
+                    max_cycles,
+                    &signal,
+                )?;
+                if args.is_present("trace-syscalls") {
+                    let listen_port: usize = args.value_of("listen").unwrap().parse()?;
+                    let trace = record_syscalls(
+                        &context,
+                        template_path,
+                        contract_name,
+                        build_env,
+                        script_group_type,
+                        cell_index,
+                        cell_type,
+                        max_cycles,
+                        listen_port,
+                        &signal,
+                    )?;
+                    let names = CellNames::from_template(
+                        template_path,
+                        script_group_type,
+                        cell_index,
+                        cell_type,
+                    )?;
+                    println!("------------------------------");
+                    for line in describe_syscalls(&trace, &names) {
+                        println!("{}", line);
+                    }
+                }
+                println!("------------------------------");
+                println!("{}", serde_yaml::to_string(&result)?);
+                if result.exit_code != Some(0) {
+                    exit(1);
+                }
+            }
+            ("profile", Some(args)) => {
+                let context = Context::load()?;
+                let template_path = args.value_of("template-file").expect("template file");
+                let build_env: BuildEnv = if args.is_present("release") {
+                    BuildEnv::Release
+                } else {
+                    BuildEnv::Debug
+                };
+                let contract_name = args.value_of("name").expect("contract name");
+                let contract = match context
+                    .config
+                    .contracts
+                    .iter()
+                    .find(|c| contract_name == c.name)
+                {
+                    Some(c) => c.clone(),
+                    None => return Err(anyhow!("can't find contract '{}'", contract_name)),
+                };
+                let script_group_type = args.value_of("script-group-type").unwrap();
+                let cell_index: usize = args.value_of("cell-index").unwrap().parse()?;
+                let cell_type = args.value_of("cell-type").unwrap();
+                let max_cycles: u64 = args.value_of("max-cycles").unwrap().parse()?;
+                let limit: usize = args.value_of("limit").unwrap().parse()?;
+                let profile = profile_script(
+                    &context,
+                    template_path,
+                    &contract,
+                    build_env,
+                    script_group_type,
+                    cell_index,
+                    cell_type,
+                    max_cycles,
+                    args.is_present("flamegraph"),
+                    &signal,
+                )?;
+                profile.print_table(limit);
+            }
+            ("record", Some(args)) => {
+                let context = Context::load()?;
+                let template_path = args.value_of("template-file").expect("template file");
+                let build_env: BuildEnv = if args.is_present("release") {
+                    BuildEnv::Release
+                } else {
+                    BuildEnv::Debug
+                };
+                let contract_name = args.value_of("name").expect("contract name");
+                let contract = match context
+                    .config
+                    .contracts
+                    .iter()
+                    .find(|c| contract_name == c.name)
+                {
+                    Some(c) => c.clone(),
+                    None => return Err(anyhow!("can't find contract '{}'", contract_name)),
+                };
+                let script_group_type = args.value_of("script-group-type").unwrap();
+                let cell_index: usize = args.value_of("cell-index").unwrap().parse()?;
+                let cell_type = args.value_of("cell-type").unwrap();
+                let max_cycles: u64 = args.value_of("max-cycles").unwrap().parse()?;
+                let listen_port: usize = args.value_of("listen").unwrap().parse()?;
+                let trace_path = record_trace(
+                    &context,
+                    template_path,
+                    &contract,
+                    build_env,
+                    script_group_type,
+                    cell_index,
+                    cell_type,
+                    max_cycles,
+                    listen_port,
+                    &signal,
+                )?;
+                println!("Write trace to {:?}", trace_path);
+            }
+            ("replay", Some(args)) => {
+                let context = Context::load()?;
+                let trace_path = args.value_of("trace-file").expect("trace file");
+                let build_env: BuildEnv = if args.is_present("release") {
+                    BuildEnv::Release
+                } else {
+                    BuildEnv::Debug
+                };
+                let contract_name = args.value_of("name").expect("contract name");
+                let contract = match context
+                    .config
+                    .contracts
+                    .iter()
+                    .find(|c| contract_name == c.name)
+                {
+                    Some(c) => c.clone(),
+                    None => return Err(anyhow!("can't find contract '{}'", contract_name)),
+                };
+                let trace = Trace::load(trace_path)?;
+                Replayer::new(&context, trace, &contract, build_env, &signal)?.run()?;
+            }
+            ("check-template", Some(args)) => {
+                let context = Context::load()?;
+                let template_path = args.value_of("template-file").expect("template file");
+                let build_env: BuildEnv = if args.is_present("release") {
+                    BuildEnv::Release
+                } else {
+                    BuildEnv::Debug
+                };
+                let errors = debugger::check_template(&context, build_env, template_path)?;
+                if errors.is_empty() {
+                    println!("All template marks are resolved");
+                } else {
+                    for (mark, err) in &errors {
+                        println!("{{{{{}}}}}\t{}", mark, err);
+                    }
+                    return Err(anyhow!("{} unresolved template marks", errors.len()));
+                }
+            }
             (command, _) => {
                 eprintln!("unknown debugger subcommand '{}'", command);
                 eprintln!("{}", help_str);
@@ -0,0 +1,235 @@
+//! Cargo cache of the build image
+//!
+//! By default all projects share the docker volume `capsule-cache` mounted as `/root/.cargo`.
+//! A project can use its own volume, or bind mount a host dir, e.g. to persist the cache in CI:
+//!
+//! ```toml
+//! [cache]
+//! per_project = true
+//! # host_dir = ".cache/cargo"
+//! ```
+//!
+//! The env `CAPSULE_CACHE_DIR` overrides the config with a host dir.
+//! Host dirs only hold `registry` and `git` of cargo, tools of the image are kept.
+
+use crate::project_context::Context;
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::util::docker::DockerCommand;
+use anyhow::{anyhow, Result};
+use ckb_tool::ckb_hash::blake2b_256;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const DOCKER_BIN: &str = "docker";
+/// shared docker volume, also the prefix of per-project volumes
+pub const CARGO_CACHE_VOLUME: &str = "capsule-cache";
+pub const CARGO_CACHE_CONTAINER_PATH: &str = "/root/.cargo";
+pub const CACHE_DIR_ENV: &str = "CAPSULE_CACHE_DIR";
+/// cache dirs of cargo, downloaded crates and git dependencies
+const CACHE_SUB_DIRS: &[&str] = &["registry", "git"];
+/// dirs which are extracted from downloaded crates and git databases
+const PRUNE_DIRS: &[&str] = &["registry/src", "git/checkouts"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CargoCache {
+    /// docker volume mounted as the cargo home
+    Volume(String),
+    /// host dir, `registry` and `git` are mounted into the cargo home
+    HostDir(PathBuf),
+}
+
+impl fmt::Display for CargoCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CargoCache::Volume(name) => write!(f, "docker volume {}", name),
+            CargoCache::HostDir(dir) => write!(f, "host dir {:?}", dir),
+        }
+    }
+}
+
+impl CargoCache {
+    fn from_env() -> Option<Self> {
+        env::var(CACHE_DIR_ENV)
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| {
+                // relative dirs are resolved against the working dir, docker needs absolute paths
+                let dir = env::current_dir()
+                    .map(|cwd| cwd.join(&dir))
+                    .unwrap_or_else(|_| PathBuf::from(dir));
+                CargoCache::HostDir(dir)
+            })
+    }
+
+    /// cache of commands outside projects
+    pub fn shared() -> Self {
+        Self::from_env().unwrap_or_else(|| CargoCache::Volume(CARGO_CACHE_VOLUME.to_string()))
+    }
+
+    /// cache of the project, the env is preferred to the project config
+    pub fn for_project(context: &Context) -> Self {
+        if let Some(cache) = Self::from_env() {
+            return cache;
+        }
+        let config = &context.config.cache;
+        if let Some(dir) = config.host_dir.as_ref() {
+            return CargoCache::HostDir(context.project_path.join(dir));
+        }
+        if config.per_project {
+            return CargoCache::Volume(project_volume_name(context));
+        }
+        CargoCache::Volume(CARGO_CACHE_VOLUME.to_string())
+    }
+
+    /// docker volumes, pairs of the source and the container path
+    pub fn mounts(&self) -> Vec<(String, String)> {
+        match self {
+            CargoCache::Volume(name) => {
+                vec![(name.clone(), CARGO_CACHE_CONTAINER_PATH.to_string())]
+            }
+            CargoCache::HostDir(dir) => CACHE_SUB_DIRS
+                .iter()
+                .map(|sub_dir| {
+                    (
+                        dir.join(sub_dir).to_str().expect("path").to_string(),
+                        format!("{}/{}", CARGO_CACHE_CONTAINER_PATH, sub_dir),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// create host dirs, otherwise docker creates them as root
+    fn prepare(&self) -> Result<()> {
+        if let CargoCache::HostDir(dir) = self {
+            for sub_dir in CACHE_SUB_DIRS {
+                fs::create_dir_all(dir.join(sub_dir))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn volume_args(&self) -> Result<Vec<String>> {
+        self.prepare()?;
+        Ok(self
+            .mounts()
+            .into_iter()
+            .map(|(source, container)| format!("-v{}:{}", source, container))
+            .collect())
+    }
+
+    fn run(&self, shell_cmd: String, signal: &Signal) -> Result<()> {
+        self.prepare()?;
+        let code_path = env::current_dir()?;
+        DockerCommand::with_config(
+            DOCKER_IMAGE.to_string(),
+            code_path.to_str().expect("path").to_string(),
+        )
+        .cargo_cache(self.clone())
+        .run(shell_cmd, signal)
+    }
+
+    fn container_dirs(dirs: &[&str]) -> String {
+        dirs.iter()
+            .map(|dir| format!("{}/{}", CARGO_CACHE_CONTAINER_PATH, dir))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// print the location and size of the cache
+    pub fn info(&self, signal: &Signal) -> Result<()> {
+        println!("Cargo cache: {}", self);
+        self.run(
+            format!(
+                "du -sh {} 2>/dev/null || true",
+                Self::container_dirs(CACHE_SUB_DIRS)
+            ),
+            signal,
+        )
+    }
+
+    /// remove extracted sources and git checkouts, downloaded crates are kept
+    pub fn prune(&self, signal: &Signal) -> Result<()> {
+        self.run(
+            format!("rm -rf {}", Self::container_dirs(PRUNE_DIRS)),
+            signal,
+        )
+    }
+
+    /// remove the whole cache
+    pub fn clean(&self, signal: &Signal) -> Result<()> {
+        match self {
+            CargoCache::Volume(name) => remove_volume(name),
+            CargoCache::HostDir(_) => {
+                let dirs: Vec<_> = CACHE_SUB_DIRS
+                    .iter()
+                    .map(|dir| format!("{}/{}/*", CARGO_CACHE_CONTAINER_PATH, dir))
+                    .collect();
+                self.run(format!("rm -rf {}", dirs.join(" ")), signal)
+            }
+        }
+    }
+}
+
+/// volume of the project, the dir name is kept for readability
+pub fn project_volume_name(context: &Context) -> String {
+    let path = context.project_path.to_string_lossy();
+    let hash = blake2b_256(path.as_bytes());
+    let name: String = context
+        .project_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    format!(
+        "{}-{}-{}",
+        CARGO_CACHE_VOLUME,
+        name,
+        faster_hex::hex_string(&hash[..4]).expect("hex")
+    )
+}
+
+/// shared and per-project cache volumes
+pub fn list_volumes() -> Result<Vec<String>> {
+    let output = Command::new(DOCKER_BIN)
+        .args(&[
+            "volume",
+            "ls",
+            "--format",
+            "{{.Name}}",
+            "--filter",
+            &format!("name={}", CARGO_CACHE_VOLUME),
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "failed to list docker volumes: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        // the filter matches substrings
+        .filter(|name| name.starts_with(CARGO_CACHE_VOLUME))
+        .map(|name| name.to_string())
+        .collect())
+}
+
+pub fn remove_volume(name: &str) -> Result<()> {
+    let status = Command::new(DOCKER_BIN)
+        .args(&["volume", "rm", name])
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!(
+            "failed to remove docker volume {}, it may be used by a running container",
+            name
+        ));
+    }
+    Ok(())
+}
@@ -1,12 +1,106 @@
+//! Checks of the environment and dependencies
+//!
+//! Docker, its daemon and git are required, other tools only enable some features.
+//! `capsule check` exits with an error if a required check fails.
+
+use crate::cache::{CargoCache, CARGO_CACHE_CONTAINER_PATH};
+use crate::generator::TEMPLATES;
+use crate::molecule::{MOLECULEC_BIN, MOLECULE_VERSION};
+use crate::project_context::Context;
+use crate::recipe::rust::DOCKER_IMAGE;
 use anyhow::{anyhow, Result};
+use ckb_tool::rpc_client::RpcClient;
 use log::warn;
+use serde::Serialize;
 use std::fmt;
+use std::fs;
 use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DOCKER_BIN: &str = "docker";
+const GIT_BIN: &str = "git";
+const CKB_CLI_BIN: &str = "ckb-cli";
+const CKB_DEBUGGER_BIN: &str = "ckb-debugger";
+const C_MAKEFILE: &str = "contracts/c/Makefile";
+/// free space of the cargo cache, dependencies and targets need a few GBs
+const MIN_CACHE_FREE_SPACE: u64 = 2 * 1024 * 1024 * 1024;
+/// the node is considered syncing if the tip is older than this
+const MAX_TIP_AGE_SECS: u64 = 10 * 60;
 
 fn check_cmd(program: &str, arg: &str) -> Result<Output> {
     Command::new(program).arg(arg).output().map_err(Into::into)
 }
 
+/// stdout of a successful command
+fn cmd_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warning,
+    Missing,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Warning => write!(f, "warning"),
+            Status::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    /// capsule can't work without it
+    pub required: bool,
+    pub version: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl Check {
+    fn new(name: &str, status: Status, required: bool) -> Self {
+        Check {
+            name: name.to_string(),
+            status,
+            required,
+            version: None,
+            detail: None,
+        }
+    }
+
+    fn version(mut self, version: String) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    fn detail<S: ToString>(mut self, detail: S) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    /// a required check is missing
+    pub fn is_failed(&self) -> bool {
+        self.required && self.status == Status::Missing
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    ok: bool,
+    checks: &'a [Check],
+}
+
 pub struct Checker {
     pub docker: bool,
     pub ckb_cli: Option<Vec<u8>>,
@@ -14,10 +108,10 @@ pub struct Checker {
 
 impl Checker {
     pub fn build() -> Result<Self> {
-        let docker = check_cmd("docker", "version")
+        let docker = check_cmd(DOCKER_BIN, "version")
             .map(|output| output.status.success())
             .unwrap_or(false);
-        let ckb_cli = check_cmd("ckb-cli", "--version")
+        let ckb_cli = check_cmd(CKB_CLI_BIN, "--version")
             .map(|output| output.stdout)
             .ok();
         Ok(Checker { docker, ckb_cli })
@@ -43,33 +137,246 @@ impl Checker {
         Ok(())
     }
 
-    pub fn print_report(&self) {
-        println!("------------------------------");
-        if self.docker {
-            println!("docker\tinstalled");
+    /// run all checks, `rpc_url` is the CKB node used by deployment
+    pub fn run_checks(&self, rpc_url: &str) -> Vec<Check> {
+        let mut checks = Vec::new();
+
+        // docker
+        let docker = match cmd_stdout(DOCKER_BIN, &["--version"]) {
+            Some(version) => Check::new("docker", Status::Ok, true).version(version),
+            None => Check::new("docker", Status::Missing, true)
+                .detail("please install docker https://docs.docker.com/get-docker/"),
+        };
+        let docker_installed = docker.status == Status::Ok;
+        checks.push(docker);
+        let daemon = if !docker_installed {
+            Check::new("docker-daemon", Status::Missing, true).detail("docker is not installed")
         } else {
-            println!("docker\tnot found - Please install docker");
-        }
-        if self.ckb_cli.is_some() {
-            match Version::parse_with_prefix("ckb-cli", self.ckb_cli.clone().unwrap()) {
-                Ok(v) if v >= REQUIRED_CKB_CLI_VERSION => {
-                    println!("ckb-cli\tinstalled {}", v);
+            match cmd_stdout(DOCKER_BIN, &["info", "--format", "{{.ServerVersion}}"]) {
+                Some(version) => Check::new("docker-daemon", Status::Ok, true).version(version),
+                None => Check::new("docker-daemon", Status::Missing, true).detail(
+                    "can't connect to the docker daemon, please start it or check permissions",
+                ),
+            }
+        };
+        let daemon_running = daemon.status == Status::Ok;
+        checks.push(daemon);
+
+        // build images
+        let mut images = vec![DOCKER_IMAGE.to_string()];
+        images.extend(c_builder_image());
+        let mut rust_image_pulled = false;
+        for image in images {
+            let name = format!("image {}", image);
+            let check = if !daemon_running {
+                Check::new(&name, Status::Warning, false).detail("docker daemon is not running")
+            } else {
+                match cmd_stdout(
+                    DOCKER_BIN,
+                    &[
+                        "image",
+                        "inspect",
+                        "--format",
+                        "{{join .RepoDigests \",\"}}",
+                        &image,
+                    ],
+                ) {
+                    Some(digests) => {
+                        rust_image_pulled |= image == DOCKER_IMAGE;
+                        Check::new(&name, Status::Ok, false).detail(digests)
+                    }
+                    None => Check::new(&name, Status::Warning, false)
+                        .detail("not pulled, it's pulled on the first build"),
                 }
-                Ok(v) => {
-                    println!(
-                        "ckb-cli\tinstalled {} (required {})",
-                        v, REQUIRED_CKB_CLI_VERSION
-                    );
+            };
+            checks.push(check);
+        }
+
+        // git
+        checks.push(match cmd_stdout(GIT_BIN, &["--version"]) {
+            Some(version) => Check::new("git", Status::Ok, true)
+                .version(version.trim_start_matches("git version").trim().to_string()),
+            None => Check::new("git", Status::Missing, true)
+                .detail("git is used to create projects and fetch dependencies"),
+        });
+
+        // tools in the build image
+        let image_tool = |name: &str, docker_args: &[&str], cmd: &str| {
+            if !rust_image_pulled {
+                return None;
+            }
+            let mut args = vec!["run", "--rm"];
+            args.extend_from_slice(docker_args);
+            args.extend_from_slice(&[DOCKER_IMAGE, "sh", "-c", cmd]);
+            cmd_stdout(DOCKER_BIN, &args)
+                .map(|output| output.trim_start_matches(name).trim().to_string())
+        };
+        checks.push(
+            match image_tool(
+                CKB_DEBUGGER_BIN,
+                &[],
+                &format!("{} --version", CKB_DEBUGGER_BIN),
+            ) {
+                Some(version) => Check::new(CKB_DEBUGGER_BIN, Status::Ok, false).version(version),
+                None => Check::new(CKB_DEBUGGER_BIN, Status::Warning, false)
+                    .detail("not found in the build image, debugging is disabled"),
+            },
+        );
+        checks.push(
+            match image_tool(MOLECULEC_BIN, &[], &format!("{} --version", MOLECULEC_BIN)) {
+                Some(version) if version.starts_with(MOLECULE_VERSION) => {
+                    Check::new(MOLECULEC_BIN, Status::Ok, false).version(version)
                 }
-                Err(_) => {
-                    println!("ckb-cli\tinstalled (unknown version)");
+                Some(version) => Check::new(MOLECULEC_BIN, Status::Warning, false)
+                    .version(version)
+                    .detail(format!("required {}.x", MOLECULE_VERSION)),
+                None => Check::new(MOLECULEC_BIN, Status::Warning, false)
+                    .detail("not found in the build image, schemas can't be compiled"),
+            },
+        );
+
+        // cargo cache, the cache of the current project or the shared one
+        let cache = Context::load()
+            .map(|context| CargoCache::for_project(&context))
+            .unwrap_or_else(|_| CargoCache::shared());
+        let cache_free = cache.volume_args().ok().and_then(|volume_args| {
+            let volume_args: Vec<_> = volume_args.iter().map(|arg| arg.as_str()).collect();
+            image_tool(
+                "",
+                &volume_args,
+                &format!("df -Pk {}/registry", CARGO_CACHE_CONTAINER_PATH),
+            )
+        });
+        checks.push(match cache_free.and_then(|df| parse_df(&df)) {
+            Some(free) if free >= MIN_CACHE_FREE_SPACE => Check::new(
+                "cargo-cache",
+                Status::Ok,
+                false,
+            )
+            .detail(format!("{} free in {}", human_bytes(free), cache)),
+            Some(free) => Check::new("cargo-cache", Status::Warning, false).detail(format!(
+                "only {} free in {}, required {}, run `capsule cache prune` to free space",
+                human_bytes(free),
+                cache,
+                human_bytes(MIN_CACHE_FREE_SPACE)
+            )),
+            None => Check::new("cargo-cache", Status::Warning, false)
+                .detail("can't check free space, the build image is not available"),
+        });
+
+        // deployment
+        checks.push(match self.ckb_cli.clone() {
+            Some(output) => match Version::parse_with_prefix("ckb-cli", output) {
+                Ok(v) if v >= REQUIRED_CKB_CLI_VERSION => {
+                    Check::new(CKB_CLI_BIN, Status::Ok, false).version(v.to_string())
                 }
-            }
-        } else {
-            println!("ckb-cli\tnot found - The deployment feature is disabled");
+                Ok(v) => Check::new(CKB_CLI_BIN, Status::Warning, false)
+                    .version(v.to_string())
+                    .detail(format!("required {}", REQUIRED_CKB_CLI_VERSION)),
+                Err(_) => Check::new(CKB_CLI_BIN, Status::Warning, false).detail("unknown version"),
+            },
+            None => Check::new(CKB_CLI_BIN, Status::Warning, false)
+                .detail("not found, the deployment feature is disabled"),
+        });
+        checks.push(check_node(rpc_url));
+        checks
+    }
+}
+
+/// builder image of C contracts, the project Makefile is preferred
+fn c_builder_image() -> Option<String> {
+    let makefile = Context::load()
+        .ok()
+        .and_then(|context| fs::read_to_string(context.project_path.join(C_MAKEFILE)).ok())
+        .or_else(|| {
+            TEMPLATES
+                .render("c/Makefile", &tera::Context::default())
+                .ok()
+        })?;
+    makefile
+        .lines()
+        .find(|line| line.starts_with("BUILDER_DOCKER"))
+        .and_then(|line| line.splitn(2, ":=").nth(1))
+        .map(|image| image.trim().to_string())
+}
+
+/// available bytes from the output of `df -Pk`
+fn parse_df(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let available: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available * 1024)
+}
+
+fn human_bytes(bytes: u64) -> String {
+    format!("{:.1}GB", bytes as f64 / (1024 * 1024 * 1024) as f64)
+}
+
+fn check_node(rpc_url: &str) -> Check {
+    let name = "ckb-node";
+    let rpc_client = RpcClient::new(rpc_url);
+    let header = match rpc_client.inner().get_tip_header() {
+        Ok(header) => header,
+        Err(err) => {
+            return Check::new(name, Status::Warning, false).detail(format!(
+                "can't connect to {}: {:?}, the deployment feature is disabled",
+                rpc_url, err
+            ));
+        }
+    };
+    let tip_number = header.inner.number.value();
+    let tip_secs = header.inner.timestamp.value() / 1000;
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let age = now_secs.saturating_sub(tip_secs);
+    if age > MAX_TIP_AGE_SECS {
+        Check::new(name, Status::Warning, false).detail(format!(
+            "{} tip #{} is {} minutes old, the node may be syncing",
+            rpc_url,
+            tip_number,
+            age / 60
+        ))
+    } else {
+        Check::new(name, Status::Ok, false)
+            .detail(format!("{} synced, tip #{}", rpc_url, tip_number))
+    }
+}
+
+/// print checks as a table, returns names of failed required checks
+pub fn print_report(checks: &[Check]) -> Vec<String> {
+    println!("------------------------------");
+    for check in checks {
+        let mut line = format!("{}\t{}", check.name, check.status);
+        if let Some(version) = check.version.as_ref() {
+            line.push_str(&format!(" {}", version));
         }
-        println!("------------------------------");
+        if let Some(detail) = check.detail.as_ref() {
+            line.push_str(&format!(" - {}", detail));
+        }
+        println!("{}", line);
     }
+    println!("------------------------------");
+    failed_checks(checks)
+}
+
+/// print checks as JSON, returns names of failed required checks
+pub fn print_json_report(checks: &[Check]) -> Result<Vec<String>> {
+    let failed = failed_checks(checks);
+    let report = JsonReport {
+        ok: failed.is_empty(),
+        checks,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(failed)
+}
+
+fn failed_checks(checks: &[Check]) -> Vec<String> {
+    checks
+        .iter()
+        .filter(|check| check.is_failed())
+        .map(|check| check.name.clone())
+        .collect()
 }
 
 const REQUIRED_CKB_CLI_VERSION: Version = Version(0, 34, 0);
@@ -8,8 +8,10 @@ use std::str::FromStr;
 #[derive(PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Copy)]
 pub enum TemplateType {
     Rust,
+    RustSharedLib,
     C,
     CSharedLib,
+    Cpp,
 }
 
 impl FromStr for TemplateType {
@@ -18,8 +20,10 @@ impl FromStr for TemplateType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let template_type = match s.to_lowercase().as_str() {
             "rust" => TemplateType::Rust,
+            "rust-sharedlib" => TemplateType::RustSharedLib,
             "c" => TemplateType::C,
             "c-sharedlib" => TemplateType::CSharedLib,
+            "cpp" => TemplateType::Cpp,
             _ => {
                 return Err(anyhow!("Unexpected template type '{}'", s));
             }
@@ -33,6 +37,14 @@ impl FromStr for TemplateType {
 pub struct Contract {
     pub name: String,
     pub template_type: TemplateType,
+    // extra flags of C contracts
+    #[serde(default)]
+    pub cflags: Option<String>,
+    #[serde(default)]
+    pub ldflags: Option<String>,
+    // contracts built before this one, their code hashes are generated into the sources
+    #[serde(default)]
+    pub dependencies: Vec<String>,
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
@@ -41,6 +53,53 @@ pub struct RustConfig {
     pub workspace_dir: Option<PathBuf>, // relative path of workspace dir, default is the project dir
 }
 
+/// C dependency under `contracts/c/deps`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CDependency {
+    pub name: String,
+    // git repository url or tarball url (`.tar.gz`)
+    pub source: String,
+    // git commit id, ignored by tarball
+    #[serde(default)]
+    pub revision: Option<String>,
+    // blake2b hash of the dependency files
+    #[serde(default)]
+    pub checksum: Option<String>,
+    // committed into the project by `capsule deps vendor`, so it's not ignored by git
+    #[serde(default)]
+    pub vendored: bool,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct MoleculeConfig {
+    // paths of `.mol` schema files, relative to the project dir
+    #[serde(default)]
+    pub schemas: Vec<PathBuf>,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ConstantsConfig {
+    // path of the generated constants crate, relative to the project dir,
+    // constants are generated on build and deploy if it is set
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    // also generate `constants.json` and `constants.ts` into the crate dir
+    #[serde(default)]
+    pub json: bool,
+    #[serde(default)]
+    pub typescript: bool,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    // use a cargo cache volume of the project instead of the shared `capsule-cache`
+    #[serde(default)]
+    pub per_project: bool,
+    // bind mount a host dir as the cargo cache, relative to the project dir
+    #[serde(default)]
+    pub host_dir: Option<PathBuf>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
@@ -50,6 +109,14 @@ pub struct Config {
     pub deployment: PathBuf, // path of deployment config file
     #[serde(default)]
     pub rust: RustConfig,
+    #[serde(default)]
+    pub c_deps: Vec<CDependency>,
+    #[serde(default)]
+    pub molecule: MoleculeConfig,
+    #[serde(default)]
+    pub constants: ConstantsConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
 }
 
 // Deployment
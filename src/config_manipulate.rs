@@ -1,8 +1,10 @@
 //! functions manipulate config file
 
-use crate::config::TemplateType;
+use crate::config::{CDependency, TemplateType};
 use anyhow::{anyhow, Result};
-pub use toml_edit::{array, value, Document, Table};
+pub use toml_edit::{
+    array, table, value, Array, ArrayOfTables, Document, InlineTable, Item, Table,
+};
 
 pub fn append_contract(
     doc: &mut Document,
@@ -29,3 +31,233 @@ pub fn append_cargo_workspace_member(doc: &mut Document, name: String) -> Result
     members.push(name);
     Ok(())
 }
+
+/// append a dependency to a Cargo manifest, returns false if it's already exists
+pub fn append_cargo_dependency(doc: &mut Document, name: &str, version: &str) -> Result<bool> {
+    let dependencies = doc["dependencies"]
+        .or_insert(table())
+        .as_table_mut()
+        .ok_or(anyhow!("no 'dependencies' section"))?;
+    if dependencies.contains_key(name) {
+        return Ok(false);
+    }
+    dependencies[name] = value(version);
+    Ok(true)
+}
+
+/// append a dependency with `default-features = false`, used by `no_std` contracts
+pub fn append_cargo_no_std_dependency(
+    doc: &mut Document,
+    name: &str,
+    version: &str,
+) -> Result<bool> {
+    let dependencies = doc["dependencies"]
+        .or_insert(table())
+        .as_table_mut()
+        .ok_or(anyhow!("no 'dependencies' section"))?;
+    if dependencies.contains_key(name) {
+        return Ok(false);
+    }
+    let mut dependency = InlineTable::default();
+    dependency.get_or_insert("version", version);
+    dependency.get_or_insert("default-features", false);
+    dependencies[name] = value(dependency);
+    Ok(true)
+}
+
+/// append or replace a C dependency
+pub fn set_c_dependency(doc: &mut Document, dep: &CDependency) -> Result<()> {
+    let deps = doc["c_deps"]
+        .or_insert(array())
+        .as_array_of_tables_mut()
+        .ok_or(anyhow!("'c_deps' is not an array of tables"))?;
+    let index = (0..deps.len()).find(|i| {
+        deps.get(*i)
+            .and_then(|t| t["name"].as_str())
+            .map(|name| name == dep.name)
+            .unwrap_or(false)
+    });
+    let table = match index {
+        Some(i) => deps.get_mut(i).expect("dep"),
+        None => deps.append(Table::new()),
+    };
+    table["name"] = value(dep.name.as_str());
+    table["source"] = value(dep.source.as_str());
+    if let Some(revision) = dep.revision.as_ref() {
+        table["revision"] = value(revision.as_str());
+    }
+    if let Some(checksum) = dep.checksum.as_ref() {
+        table["checksum"] = value(checksum.as_str());
+    }
+    if dep.vendored {
+        table["vendored"] = value(true);
+    }
+    Ok(())
+}
+
+/// index of the table which `name` field is `name`
+fn find_named_table(tables: &ArrayOfTables, name: &str) -> Option<usize> {
+    (0..tables.len()).find(|i| {
+        tables
+            .get(*i)
+            .and_then(|t| t["name"].as_str())
+            .map(|n| n == name)
+            .unwrap_or(false)
+    })
+}
+
+/// remove tables matching the filter, toml_edit can't remove a table from the array
+fn retain_tables<F: Fn(&Table) -> bool>(item: &mut Item, f: F) {
+    if let Some(tables) = item.as_array_of_tables() {
+        let mut retained = ArrayOfTables::new();
+        for table in tables.iter().filter(|t| f(t)) {
+            retained.append(table.clone());
+        }
+        *item = Item::ArrayOfTables(retained);
+    }
+}
+
+/// rebuild an array of strings
+fn map_string_array<F: Fn(&str) -> Option<String>>(item: &mut Item, f: F) -> Result<()> {
+    let strings: Vec<String> = match item.as_array() {
+        Some(array) => array
+            .iter()
+            .filter_map(|v| v.as_str())
+            .filter_map(|s| f(s))
+            .collect(),
+        None => return Ok(()),
+    };
+    let mut array = Array::default();
+    for s in strings {
+        array.push(s);
+    }
+    *item = value(array);
+    Ok(())
+}
+
+/// map `dependencies` of all contracts
+fn map_contract_dependencies<F: Fn(&str) -> Option<String>>(
+    doc: &mut Document,
+    f: F,
+) -> Result<()> {
+    if let Some(contracts) = doc["contracts"].as_array_of_tables_mut() {
+        for i in 0..contracts.len() {
+            let contract = contracts.get_mut(i).expect("contract");
+            if contract.contains_key("dependencies") {
+                map_string_array(&mut contract["dependencies"], &f)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// rename the contract and references in dependencies of other contracts
+pub fn rename_contract(doc: &mut Document, name: &str, new_name: &str) -> Result<()> {
+    let contracts = doc["contracts"]
+        .as_array_of_tables_mut()
+        .ok_or(anyhow!("no 'contracts' section"))?;
+    let index =
+        find_named_table(contracts, name).ok_or(anyhow!("can't find contract '{}'", name))?;
+    contracts.get_mut(index).expect("contract")["name"] = value(new_name);
+    map_contract_dependencies(doc, |dep| {
+        if dep == name {
+            Some(new_name.to_string())
+        } else {
+            Some(dep.to_string())
+        }
+    })
+}
+
+/// remove the contract and references in dependencies of other contracts
+pub fn remove_contract(doc: &mut Document, name: &str) -> Result<()> {
+    retain_tables(&mut doc["contracts"], |t| t["name"].as_str() != Some(name));
+    map_contract_dependencies(doc, |dep| {
+        if dep == name {
+            None
+        } else {
+            Some(dep.to_string())
+        }
+    })
+}
+
+pub fn rename_cargo_workspace_member(
+    doc: &mut Document,
+    member: &str,
+    new_member: &str,
+) -> Result<()> {
+    map_string_array(&mut doc["workspace"]["members"], |m| {
+        if m == member {
+            Some(new_member.to_string())
+        } else {
+            Some(m.to_string())
+        }
+    })
+}
+
+pub fn remove_cargo_workspace_member(doc: &mut Document, member: &str) -> Result<()> {
+    map_string_array(&mut doc["workspace"]["members"], |m| {
+        if m == member {
+            None
+        } else {
+            Some(m.to_string())
+        }
+    })
+}
+
+/// rename a cell of deployment.toml and the file location `build/<env>/<name>`
+pub fn rename_deployment_cell(doc: &mut Document, name: &str, new_name: &str) -> Result<()> {
+    if let Some(cells) = doc["cells"].as_array_of_tables_mut() {
+        if let Some(index) = find_named_table(cells, name) {
+            let cell = cells.get_mut(index).expect("cell");
+            cell["name"] = value(new_name);
+            let file = cell["location"]
+                .as_value_mut()
+                .and_then(|v| v.as_inline_table_mut())
+                .and_then(|t| t.get_mut("file"));
+            if let Some(file) = file {
+                let renamed = file.as_str().and_then(|path| {
+                    let (dir, file_name) = path.split_at(path.rfind('/').map(|i| i + 1)?);
+                    if file_name == name {
+                        Some(format!("{}{}", dir, new_name))
+                    } else {
+                        None
+                    }
+                });
+                if let Some(renamed) = renamed {
+                    *file = renamed.as_str().into();
+                }
+            }
+        }
+    }
+    if let Some(groups) = doc["dep_groups"].as_array_of_tables_mut() {
+        for i in 0..groups.len() {
+            let group = groups.get_mut(i).expect("dep group");
+            map_string_array(&mut group["cells"], |cell| {
+                if cell == name {
+                    Some(new_name.to_string())
+                } else {
+                    Some(cell.to_string())
+                }
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// remove a cell of deployment.toml and its references in dep groups
+pub fn remove_deployment_cell(doc: &mut Document, name: &str) -> Result<()> {
+    retain_tables(&mut doc["cells"], |t| t["name"].as_str() != Some(name));
+    if let Some(groups) = doc["dep_groups"].as_array_of_tables_mut() {
+        for i in 0..groups.len() {
+            let group = groups.get_mut(i).expect("dep group");
+            map_string_array(&mut group["cells"], |cell| {
+                if cell == name {
+                    None
+                } else {
+                    Some(cell.to_string())
+                }
+            })?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,371 @@
+//! Constants of contracts for off-chain code
+//!
+//! A standalone crate is generated when the path is set in `capsule.toml`:
+//!
+//! ```toml
+//! [constants]
+//! path = "constants"
+//! json = true
+//! typescript = true
+//! ```
+//!
+//! Data hashes of `build/<env>` binaries are generated into modules `debug` and `release`,
+//! code hashes, type id hashes and out points of the latest migration of `migrations/<env>`
+//! are generated into modules `dev` and `production`. The crate is regenerated on build and deploy.
+
+use crate::deployment::manage::load_latest_recipe;
+use crate::deployment::recipe::DeploymentRecipe;
+use crate::project_context::{BuildEnv, Context, DeployEnv};
+use crate::recipe::get_recipe;
+use crate::util::fs::write_if_changed;
+use anyhow::{anyhow, Result};
+use ckb_tool::ckb_hash::blake2b_256;
+use ckb_tool::ckb_types::H256;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const GENERATED_HEADER: &str =
+    "// Generated by capsule from contract binaries and migrations, don't edit";
+const CRATE_NAME_SUFFIX: &str = "constants";
+
+const BUILD_ENVS: &[(BuildEnv, &str)] =
+    &[(BuildEnv::Debug, "debug"), (BuildEnv::Release, "release")];
+const DEPLOY_ENVS: &[(DeployEnv, &str)] = &[
+    (DeployEnv::Dev, "dev"),
+    (DeployEnv::Production, "production"),
+];
+
+/// a generated constant, name is the cell or contract name
+enum Constant {
+    CodeHash(String, [u8; 32]),
+    TypeHash(String, [u8; 32]),
+    OutPoint(String, [u8; 32], u32),
+    DepGroupOutPoint(String, [u8; 32], u32),
+}
+
+impl Constant {
+    fn name(&self) -> &str {
+        match self {
+            Constant::CodeHash(name, _)
+            | Constant::TypeHash(name, _)
+            | Constant::OutPoint(name, _, _)
+            | Constant::DepGroupOutPoint(name, _, _) => name,
+        }
+    }
+
+    /// rust identifier, names which differ only in non-alphanumeric chars have the same identifier
+    fn ident(&self) -> String {
+        let suffix = match self {
+            Constant::CodeHash(..) => "CODE_HASH",
+            Constant::TypeHash(..) => "TYPE_HASH",
+            Constant::OutPoint(..) => "OUT_POINT",
+            Constant::DepGroupOutPoint(..) => "DEP_GROUP_OUT_POINT",
+        };
+        let name: String = self
+            .name()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{}_{}", name.to_uppercase(), suffix)
+    }
+
+    fn to_rust(&self) -> String {
+        match self {
+            Constant::CodeHash(_, hash) | Constant::TypeHash(_, hash) => {
+                format!("pub const {}: [u8; 32] = {};", self.ident(), rust_bytes(hash))
+            }
+            Constant::OutPoint(_, tx_hash, index) | Constant::DepGroupOutPoint(_, tx_hash, index) => {
+                format!(
+                    "pub const {}: super::OutPoint = super::OutPoint {{\n        tx_hash: {},\n        index: {},\n    }};",
+                    self.ident(),
+                    rust_bytes(tx_hash),
+                    index
+                )
+            }
+        }
+    }
+}
+
+fn rust_bytes(hash: &[u8]) -> String {
+    let bytes: Vec<_> = hash.iter().map(|b| format!("0x{:02x}", b)).collect();
+    format!("[{}]", bytes.join(", "))
+}
+
+fn hex(hash: &[u8]) -> String {
+    let bytes: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", bytes)
+}
+
+fn h256_bytes(hash: &H256) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(hash.as_bytes());
+    bytes
+}
+
+pub struct Constants<'a> {
+    context: &'a Context,
+}
+
+impl<'a> Constants<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        Constants { context }
+    }
+
+    fn crate_path(&self) -> Option<PathBuf> {
+        self.context
+            .config
+            .constants
+            .path
+            .as_ref()
+            .map(|path| self.context.project_path.join(path))
+    }
+
+    fn crate_name(&self) -> String {
+        let project_name = self
+            .context
+            .project_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("capsule");
+        format!("{}-{}", project_name, CRATE_NAME_SUFFIX)
+    }
+
+    /// data hashes of built binaries, missing binaries are skipped
+    fn build_constants(&self, env: BuildEnv) -> Result<Vec<Constant>> {
+        let mut constants = Vec::new();
+        for contract in &self.context.config.contracts {
+            let recipe = get_recipe(self.context.clone(), contract.template_type)?;
+            let path = recipe.binary_path(contract, env);
+            if !path.is_file() {
+                continue;
+            }
+            let hash = blake2b_256(fs::read(&path)?);
+            constants.push(Constant::CodeHash(contract.name.clone(), hash));
+        }
+        Ok(constants)
+    }
+
+    /// cells and dep groups of the latest migration
+    fn deploy_constants(&self, env: DeployEnv) -> Result<Vec<Constant>> {
+        let recipe = match load_latest_recipe(&self.context.migrations_path(env))? {
+            Some(recipe) => recipe,
+            None => return Ok(Vec::new()),
+        };
+        let DeploymentRecipe {
+            cell_recipes,
+            dep_group_recipes,
+        } = recipe;
+        let mut constants = Vec::new();
+        for cell in cell_recipes {
+            constants.push(Constant::CodeHash(
+                cell.name.clone(),
+                h256_bytes(&cell.data_hash),
+            ));
+            if let Some(type_id) = cell.type_id.as_ref() {
+                constants.push(Constant::TypeHash(cell.name.clone(), h256_bytes(type_id)));
+            }
+            constants.push(Constant::OutPoint(
+                cell.name,
+                h256_bytes(&cell.tx_hash),
+                cell.index,
+            ));
+        }
+        for dep_group in dep_group_recipes {
+            constants.push(Constant::DepGroupOutPoint(
+                dep_group.name,
+                h256_bytes(&dep_group.tx_hash),
+                dep_group.index,
+            ));
+        }
+        Ok(constants)
+    }
+
+    /// all constants by module name
+    fn modules(&self) -> Result<Vec<(&'static str, Vec<Constant>)>> {
+        let mut modules = Vec::new();
+        for (env, name) in BUILD_ENVS {
+            modules.push((*name, self.build_constants(*env)?));
+        }
+        for (env, name) in DEPLOY_ENVS {
+            modules.push((*name, self.deploy_constants(*env)?));
+        }
+        Ok(modules)
+    }
+
+    fn render_lib(modules: &[(&str, Vec<Constant>)]) -> Result<String> {
+        let mut content = format!("{}\n\n#![no_std]\n", GENERATED_HEADER);
+        content.push_str(
+            "\n/// out point of a deployed cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    pub tx_hash: [u8; 32],
+    pub index: u32,
+}\n",
+        );
+        for (name, constants) in modules {
+            content.push_str(&format!("\npub mod {} {{\n", name));
+            let mut idents = HashMap::new();
+            for constant in constants {
+                let ident = constant.ident();
+                if let Some(other) = idents.insert(ident.clone(), constant.name()) {
+                    return Err(anyhow!(
+                        "'{}' and '{}' have the same constant {} in module {}, rename one of them",
+                        other,
+                        constant.name(),
+                        ident,
+                        name
+                    ));
+                }
+                content.push_str(&format!("    {}\n", constant.to_rust()));
+            }
+            content.push_str("}\n");
+        }
+        Ok(content)
+    }
+
+    fn render_json(modules: &[(&str, Vec<Constant>)]) -> Value {
+        let mut root = Map::new();
+        for (name, constants) in modules {
+            let mut module = Map::new();
+            for constant in constants {
+                let (cell_name, key, value) = match constant {
+                    Constant::CodeHash(name, hash) => (name, "code_hash", json!(hex(hash))),
+                    Constant::TypeHash(name, hash) => (name, "type_hash", json!(hex(hash))),
+                    Constant::OutPoint(name, tx_hash, index) => (
+                        name,
+                        "out_point",
+                        json!({"tx_hash": hex(tx_hash), "index": index}),
+                    ),
+                    Constant::DepGroupOutPoint(name, tx_hash, index) => (
+                        name,
+                        "dep_group_out_point",
+                        json!({"tx_hash": hex(tx_hash), "index": index}),
+                    ),
+                };
+                if let Value::Object(cell) = module
+                    .entry(cell_name.clone())
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    cell.insert(key.to_string(), value);
+                }
+            }
+            root.insert(name.to_string(), Value::Object(module));
+        }
+        Value::Object(root)
+    }
+
+    fn write(path: &Path, content: &str) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_if_changed(path, content.as_bytes())
+    }
+
+    /// regenerate the constants crate, does nothing if the path is not set
+    pub fn generate(&self) -> Result<()> {
+        let crate_path = match self.crate_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let modules = self.modules()?;
+        // the crate is standalone, it isn't a member of the project workspace
+        let manifest = format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[dependencies]\n\n[workspace]\n",
+            self.crate_name()
+        );
+        Self::write(&crate_path.join("Cargo.toml"), &manifest)?;
+        Self::write(
+            &crate_path.join("src").join("lib.rs"),
+            &Self::render_lib(&modules)?,
+        )?;
+        let config = &self.context.config.constants;
+        if config.json || config.typescript {
+            let json = serde_json::to_string_pretty(&Self::render_json(&modules))?;
+            if config.json {
+                Self::write(&crate_path.join("constants.json"), &format!("{}\n", json))?;
+            }
+            if config.typescript {
+                Self::write(
+                    &crate_path.join("constants.ts"),
+                    &format!(
+                        "{}\n\nexport const constants = {} as const;\n",
+                        GENERATED_HEADER, json
+                    ),
+                )?;
+            }
+        }
+        println!("Generated constants {:?}", crate_path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules() -> Vec<(&'static str, Vec<Constant>)> {
+        vec![
+            (
+                "debug",
+                vec![Constant::CodeHash("my-lock".to_string(), [1u8; 32])],
+            ),
+            (
+                "dev",
+                vec![
+                    Constant::TypeHash("my-lock".to_string(), [2u8; 32]),
+                    Constant::OutPoint("my-lock".to_string(), [3u8; 32], 1),
+                    Constant::DepGroupOutPoint("lock-group".to_string(), [4u8; 32], 0),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_render_lib() {
+        let content = Constants::render_lib(&modules()).expect("render");
+        assert!(content.starts_with(GENERATED_HEADER));
+        assert!(content.contains(&format!(
+            "pub mod debug {{\n    pub const MY_LOCK_CODE_HASH: [u8; 32] = {};\n}}\n",
+            rust_bytes(&[1u8; 32])
+        )));
+        assert!(content.contains(&format!(
+            "    pub const MY_LOCK_OUT_POINT: super::OutPoint = super::OutPoint {{\n        tx_hash: {},\n        index: 1,\n    }};\n",
+            rust_bytes(&[3u8; 32])
+        )));
+        assert!(content.contains("    pub const LOCK_GROUP_DEP_GROUP_OUT_POINT: super::OutPoint"));
+
+        // identifiers of `my-lock` and `my_lock` collide
+        let modules = vec![(
+            "debug",
+            vec![
+                Constant::CodeHash("my-lock".to_string(), [1u8; 32]),
+                Constant::CodeHash("my_lock".to_string(), [2u8; 32]),
+            ],
+        )];
+        let err = Constants::render_lib(&modules).expect_err("duplicate identifiers");
+        assert!(err.to_string().contains("MY_LOCK_CODE_HASH"));
+    }
+
+    #[test]
+    fn test_render_json() {
+        assert_eq!(
+            Constants::render_json(&modules()),
+            json!({
+                "debug": {
+                    "my-lock": {"code_hash": hex(&[1u8; 32])},
+                },
+                "dev": {
+                    "my-lock": {
+                        "type_hash": hex(&[2u8; 32]),
+                        "out_point": {"tx_hash": hex(&[3u8; 32]), "index": 1},
+                    },
+                    "lock-group": {
+                        "dep_group_out_point": {"tx_hash": hex(&[4u8; 32]), "index": 0},
+                    },
+                },
+            })
+        );
+    }
+}
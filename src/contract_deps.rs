@@ -0,0 +1,256 @@
+//! Dependencies between contracts
+//!
+//! A contract declares contracts it depends on in `capsule.toml`:
+//!
+//! ```toml
+//! [[contracts]]
+//! name = "my-type"
+//! template_type = "Rust"
+//! dependencies = ["my-lock"]
+//! ```
+//!
+//! Dependencies are built first, then their code hashes are generated into the
+//! dependent as `src/code_hashes.rs` of Rust contracts or `code_hashes.h` of C contracts.
+
+use crate::config::Contract;
+use crate::project_context::{BuildConfig, BuildEnv, Context, CONFIG_FILE};
+use crate::recipe::get_recipe;
+use crate::signal::Signal;
+use crate::util::elf::check_binary;
+use crate::util::fs::write_if_changed;
+use anyhow::{anyhow, Result};
+use ckb_tool::ckb_hash::blake2b_256;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+const GENERATED_HEADER: &str =
+    "// Generated by capsule from dependencies of the contract, don't edit";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+/// Selected contracts and their dependencies in build order,
+/// all contracts are selected if `names` is empty
+pub fn build_order(contracts: &[Contract], names: &[&str]) -> Result<Vec<Contract>> {
+    fn visit<'a>(
+        contract: &'a Contract,
+        contracts: &HashMap<&str, &'a Contract>,
+        marks: &mut HashMap<&'a str, Mark>,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<Contract>,
+    ) -> Result<()> {
+        let name = contract.name.as_str();
+        match marks.get(name) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                path.push(name);
+                return Err(anyhow!(
+                    "circular dependencies of contracts: {}",
+                    path.join(" -> ")
+                ));
+            }
+            None => {}
+        }
+        marks.insert(name, Mark::Visiting);
+        path.push(name);
+        for dep in &contract.dependencies {
+            let dep_contract = contracts.get(dep.as_str()).ok_or_else(|| {
+                anyhow!(
+                    "contract '{}' depends on '{}' which is not in {}",
+                    name,
+                    dep,
+                    CONFIG_FILE
+                )
+            })?;
+            visit(dep_contract, contracts, marks, path, order)?;
+        }
+        path.pop();
+        marks.insert(name, Mark::Done);
+        order.push(contract.clone());
+        Ok(())
+    }
+
+    let by_name: HashMap<&str, &Contract> =
+        contracts.iter().map(|c| (c.name.as_str(), c)).collect();
+    let mut marks = HashMap::new();
+    let mut order = Vec::new();
+    for contract in contracts
+        .iter()
+        .filter(|c| names.is_empty() || names.contains(&c.name.as_str()))
+    {
+        visit(contract, &by_name, &mut marks, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// binary paths of dependencies
+pub fn dependency_binaries(
+    context: &Context,
+    contract: &Contract,
+    build_env: BuildEnv,
+) -> Result<Vec<(String, PathBuf)>> {
+    let mut binaries = Vec::new();
+    for dep in &contract.dependencies {
+        let dep_contract = context
+            .config
+            .contracts
+            .iter()
+            .find(|c| &c.name == dep)
+            .ok_or_else(|| anyhow!("can't find dependency '{}' in {}", dep, CONFIG_FILE))?;
+        let recipe = get_recipe(context.clone(), dep_contract.template_type)?;
+        binaries.push((dep.clone(), recipe.binary_path(dep_contract, build_env)));
+    }
+    Ok(binaries)
+}
+
+/// name of the generated code hash constant of a dependency
+pub fn const_name(name: &str) -> String {
+    format!("{}_CODE_HASH", name.replace('-', "_").to_uppercase())
+}
+
+fn hex_bytes(hash: &[u8]) -> String {
+    hash.iter()
+        .map(|b| format!("0x{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// generate code hashes of dependencies into the contract sources
+pub fn write_code_hashes(
+    context: &Context,
+    contract: &Contract,
+    build_env: BuildEnv,
+) -> Result<()> {
+    if contract.dependencies.is_empty() {
+        return Ok(());
+    }
+    let mut hashes = Vec::new();
+    for (name, path) in dependency_binaries(context, contract, build_env)? {
+        let binary = fs::read(&path).map_err(|err| {
+            anyhow!(
+                "can't read binary of dependency '{}' from {:?}: {}",
+                name,
+                path,
+                err
+            )
+        })?;
+        hashes.push((name, blake2b_256(&binary)));
+    }
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    let path = recipe.code_hashes_path(contract);
+    let is_header = path.extension().and_then(|ext| ext.to_str()) == Some("h");
+    let mut content = format!("{}\n", GENERATED_HEADER);
+    if is_header {
+        let guard = format!(
+            "{}_CODE_HASHES_H",
+            contract.name.replace('-', "_").to_uppercase()
+        );
+        content.push_str(&format!(
+            "#ifndef {guard}\n#define {guard}\n\n#include <stdint.h>\n\n",
+            guard = guard
+        ));
+        for (name, hash) in &hashes {
+            content.push_str(&format!(
+                "// code hash of `{}`, data hash of the binary\nstatic const uint8_t {}[32] = {{{}}};\n\n",
+                name,
+                const_name(name),
+                hex_bytes(hash)
+            ));
+        }
+        content.push_str(&format!("#endif /* {} */\n", guard));
+    } else {
+        for (name, hash) in &hashes {
+            content.push_str(&format!(
+                "\n/// code hash of `{}`, data hash of the binary\npub const {}: [u8; 32] = [{}];\n",
+                name,
+                const_name(name),
+                hex_bytes(hash)
+            ));
+        }
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_if_changed(&path, content.as_bytes())
+}
+
+/// build the contract after generating code hashes of dependencies, then validate the binary
+pub fn build_contract(
+    context: &Context,
+    contract: &Contract,
+    config: BuildConfig,
+    signal: &Signal,
+) -> Result<()> {
+    write_code_hashes(context, contract, config.build_env)?;
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    recipe.run_build(contract, config, signal)?;
+    let require_stripped = match config.build_env {
+        BuildEnv::Debug => false,
+        BuildEnv::Release => true,
+    };
+    check_binary(
+        recipe.binary_path(contract, config.build_env),
+        require_stripped,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TemplateType;
+
+    fn contract(name: &str, dependencies: &[&str]) -> Contract {
+        Contract {
+            name: name.to_string(),
+            template_type: TemplateType::Rust,
+            cflags: None,
+            ldflags: None,
+            dependencies: dependencies.iter().map(|dep| dep.to_string()).collect(),
+        }
+    }
+
+    fn names(contracts: Vec<Contract>) -> Vec<String> {
+        contracts.into_iter().map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn test_build_order() {
+        let contracts = vec![
+            contract("a", &["b"]),
+            contract("b", &["c"]),
+            contract("c", &[]),
+            contract("d", &["c"]),
+        ];
+        assert_eq!(
+            names(build_order(&contracts, &[]).unwrap()),
+            vec!["c", "b", "a", "d"]
+        );
+        assert_eq!(
+            names(build_order(&contracts, &["b"]).unwrap()),
+            vec!["c", "b"]
+        );
+    }
+
+    #[test]
+    fn test_build_order_cycle() {
+        let contracts = vec![
+            contract("a", &["b"]),
+            contract("b", &["c"]),
+            contract("c", &["a"]),
+        ];
+        let err = build_order(&contracts, &[])
+            .err()
+            .expect("circular dependencies");
+        assert!(err.to_string().contains("a -> b -> c -> a"), "{}", err);
+    }
+
+    #[test]
+    fn test_build_order_missing_dependency() {
+        let contracts = vec![contract("a", &["b"])];
+        assert!(build_order(&contracts, &[]).is_err());
+    }
+}
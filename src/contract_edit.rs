@@ -0,0 +1,287 @@
+//! Import, rename and remove contracts of a project
+//!
+//! Edits of the contract config, sources, binaries, deployment config and tests
+//! are collected into `Changes`, so they can be previewed before writing.
+
+use crate::config::{Contract, TemplateType};
+use crate::config_manipulate::{
+    remove_contract as remove_contract_config, remove_deployment_cell,
+    rename_contract as rename_contract_config, rename_deployment_cell, Document,
+};
+use crate::contract_deps::const_name;
+use crate::generator::TEMPLATES;
+use crate::project_context::{read_config_file, BuildEnv, Context, CARGO_CONFIG_FILE, CONFIG_FILE};
+use crate::recipe::get_recipe;
+use crate::util::changes::Changes;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const FUZZ_DIR: &str = "fuzz";
+const SKELETON_TEST_TEMPLATE: &str = "rust/tests/src/skeleton.rs";
+
+#[derive(Serialize)]
+struct SkeletonTest {
+    name: String,
+}
+
+fn find_contract<'a>(context: &'a Context, name: &str) -> Result<&'a Contract> {
+    context
+        .config
+        .contracts
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or(anyhow!("can't find contract '{}' in {}", name, CONFIG_FILE))
+}
+
+/// generated test modules of the contract, the fuzz target and the skeleton test
+fn test_modules(name: &str) -> Vec<String> {
+    let name = name.replace('-', "_");
+    vec![format!("fuzz_{}", name), format!("tests_{}", name)]
+}
+
+/// rust sources of the tests crate
+fn test_sources(context: &Context) -> Result<Vec<PathBuf>> {
+    fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(&path, files)?;
+            } else if path.extension().and_then(|ext| ext.to_str()) == Some("rs") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut src_dir = context.tests_path();
+    src_dir.push("src");
+    let mut files = Vec::new();
+    if src_dir.exists() {
+        collect(&src_dir, &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// references of the contract in tests which are renamed, `load_binary("name")`,
+/// the contract of the fuzz target and template marks
+fn test_references(name: &str) -> Vec<String> {
+    vec![
+        format!("load_binary(\"{}\")", name),
+        format!("CONTRACT: &str = \"{}\";", name),
+        format!("{{{{{}.", name),
+        format!("{}/{}/", FUZZ_DIR, name),
+    ]
+}
+
+/// the name may be used for other things, such matches are left to the user
+fn possible_reference(name: &str) -> String {
+    format!("\"{}\"", name)
+}
+
+fn binary_paths(context: &Context, contract: &Contract) -> Result<Vec<PathBuf>> {
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    Ok([BuildEnv::Debug, BuildEnv::Release]
+        .iter()
+        .map(|env| recipe.binary_path(contract, *env))
+        .collect())
+}
+
+/// contracts which depend on the contract
+fn dependents<'a>(context: &'a Context, name: &str) -> Vec<&'a Contract> {
+    context
+        .config
+        .contracts
+        .iter()
+        .filter(|c| c.dependencies.iter().any(|dep| dep == name))
+        .collect()
+}
+
+fn deployment_path(context: &Context) -> PathBuf {
+    let mut path = context.project_path.clone();
+    path.push(&context.config.deployment);
+    path
+}
+
+pub fn rename_contract(context: &Context, name: &str, new_name: &str) -> Result<Changes> {
+    let contract = find_contract(context, name)?;
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    if context.config.contracts.iter().any(|c| c.name == new_name) || recipe.exists(new_name) {
+        return Err(anyhow!("contract '{}' is already exists", new_name));
+    }
+    let new_contract = Contract {
+        name: new_name.to_string(),
+        ..contract.clone()
+    };
+    let mut changes = Changes::new(&context.project_path);
+
+    changes.edit_toml(CONFIG_FILE, |doc| {
+        rename_contract_config(doc, name, new_name)
+    })?;
+    for dependent in dependents(context, name) {
+        changes.note(format!(
+            "'{}' depends on '{}', the generated `{}` is renamed to `{}` at the next build, please update its sources",
+            dependent.name,
+            name,
+            const_name(name),
+            const_name(new_name)
+        ));
+    }
+    recipe.rename_contract(contract, new_name, &mut changes)?;
+    for (path, new_path) in binary_paths(context, contract)?
+        .into_iter()
+        .zip(binary_paths(context, &new_contract)?)
+    {
+        changes.move_path(path, new_path);
+    }
+    let deployment_path = deployment_path(context);
+    if deployment_path.exists() {
+        changes.edit_toml(&deployment_path, |doc| {
+            rename_deployment_cell(doc, name, new_name)
+        })?;
+    }
+
+    // tests
+    let references = test_references(name);
+    let new_references = test_references(new_name);
+    let modules = test_modules(name);
+    let new_modules = test_modules(new_name);
+    for path in test_sources(context)? {
+        let mut content = changes.read(&path)?.unwrap_or_default();
+        for (reference, new_reference) in references.iter().zip(&new_references) {
+            content = content.replace(reference, new_reference);
+        }
+        for (module, new_module) in modules.iter().zip(&new_modules) {
+            content = content.replace(&format!("mod {};", module), &format!("mod {};", new_module));
+        }
+        if content.contains(&possible_reference(name)) {
+            changes.note(format!(
+                "{:?} contains {}, please check whether it references contract '{}'",
+                path,
+                possible_reference(name),
+                name
+            ));
+        }
+        changes.write(&path, content)?;
+    }
+    let mut src_dir = context.tests_path();
+    src_dir.push("src");
+    for (module, new_module) in modules.iter().zip(&new_modules) {
+        changes.move_path(
+            src_dir.join(format!("{}.rs", module)),
+            src_dir.join(format!("{}.rs", new_module)),
+        );
+    }
+    changes.move_path(
+        context.project_path.join(FUZZ_DIR).join(name),
+        context.project_path.join(FUZZ_DIR).join(new_name),
+    );
+    Ok(changes)
+}
+
+pub fn remove_contract(context: &Context, name: &str) -> Result<Changes> {
+    let contract = find_contract(context, name)?;
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    let mut changes = Changes::new(&context.project_path);
+
+    changes.edit_toml(CONFIG_FILE, |doc| remove_contract_config(doc, name))?;
+    for dependent in dependents(context, name) {
+        changes.note(format!(
+            "'{}' depends on '{}', it's removed from the dependencies, the generated `{}` is stale, please update its sources",
+            dependent.name,
+            name,
+            const_name(name)
+        ));
+    }
+    recipe.remove_contract(contract, &mut changes)?;
+    for path in binary_paths(context, contract)? {
+        changes.remove_path(path);
+    }
+    let deployment_path = deployment_path(context);
+    if deployment_path.exists() {
+        changes.edit_toml(&deployment_path, |doc| remove_deployment_cell(doc, name))?;
+    }
+
+    // tests, the generated test modules are removed, other references are left to the user
+    let references = test_references(name);
+    let modules = test_modules(name);
+    let mut src_dir = context.tests_path();
+    src_dir.push("src");
+    let module_paths: Vec<_> = modules
+        .iter()
+        .map(|module| src_dir.join(format!("{}.rs", module)))
+        .collect();
+    for path in test_sources(context)? {
+        if module_paths.contains(&path) {
+            changes.delete(&path)?;
+            continue;
+        }
+        let mut content = changes.read(&path)?.unwrap_or_default();
+        for module in &modules {
+            content = content
+                .replace(&format!("\n#[cfg(test)]\nmod {};\n", module), "")
+                .replace(&format!("mod {};\n", module), "");
+        }
+        if references
+            .iter()
+            .chain(&[possible_reference(name)])
+            .any(|reference| content.contains(reference))
+        {
+            changes.note(format!(
+                "{:?} references contract '{}', please edit it by hand",
+                path, name
+            ));
+        }
+        changes.write(&path, content)?;
+    }
+    changes.remove_path(context.project_path.join(FUZZ_DIR).join(name));
+    Ok(changes)
+}
+
+/// name of the imported contract, the package name of a crate or the file name of C sources
+pub fn import_name(source: &Path, template_type: TemplateType) -> Result<String> {
+    match template_type {
+        TemplateType::Rust | TemplateType::RustSharedLib => {
+            let mut cargo_path = source.to_path_buf();
+            cargo_path.push(CARGO_CONFIG_FILE);
+            let doc = read_config_file(&cargo_path)?.parse::<Document>()?;
+            doc["package"]["name"]
+                .as_str()
+                .map(|name| name.to_string())
+                .ok_or(anyhow!("can't find package name in {:?}", cargo_path))
+        }
+        _ => source
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .map(|name| name.to_string())
+            .ok_or(anyhow!("invalid source path {:?}", source)),
+    }
+}
+
+/// generate a skeleton test of the contract into tests crate if it not exists
+pub fn create_skeleton_test(context: &Context, contract: &Contract) -> Result<()> {
+    let module = test_modules(&contract.name).pop().expect("skeleton module");
+    let mut src_dir = context.tests_path();
+    src_dir.push("src");
+    let test_path = src_dir.join(format!("{}.rs", module));
+    if test_path.exists() {
+        return Ok(());
+    }
+    println!("New test {:?}", test_path);
+    let template_context = tera::Context::from_serialize(&SkeletonTest {
+        name: contract.name.clone(),
+    })?;
+    let content = TEMPLATES.render(SKELETON_TEST_TEMPLATE, &template_context)?;
+    fs::write(&test_path, content)?;
+
+    // register module
+    let lib_path = src_dir.join("lib.rs");
+    println!("Rewrite {:?}", lib_path);
+    fs::OpenOptions::new()
+        .append(true)
+        .open(lib_path)?
+        .write_all(format!("\n#[cfg(test)]\nmod {};\n", module).as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,196 @@
+//! Contracts code coverage
+//!
+//! `verify_tx` of the tests crate executes script groups again in a coverage VM when
+//! `CAPSULE_COVERAGE_DIR` is set, executed PCs of each contract are written into
+//! `build/coverage/<contract>/*.trace`, one hex address and an optional hit count per line.
+//! Addresses are mapped back to source lines through the DWARF line table of
+//! the unstripped binary, then written as a lcov report per contract.
+
+use crate::config::Contract;
+use crate::project_context::{BuildEnv, Context};
+use crate::recipe::get_recipe;
+use crate::signal::Signal;
+use crate::util::cli;
+use crate::util::dwarf::LineTable;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub const COVERAGE_DIR: &str = "build/coverage";
+pub const COVERAGE_ENV_VAR: &str = "CAPSULE_COVERAGE_DIR";
+const TRACE_EXT: &str = "trace";
+const LCOV_EXT: &str = "info";
+
+/// Coverage summary of a contract
+pub struct CoverageSummary {
+    pub name: String,
+    pub lines_found: usize,
+    pub lines_hit: usize,
+    pub report_path: PathBuf,
+}
+
+impl CoverageSummary {
+    pub fn percent(&self) -> f64 {
+        if self.lines_found == 0 {
+            return 0.0;
+        }
+        self.lines_hit as f64 * 100.0 / self.lines_found as f64
+    }
+}
+
+pub struct Coverage<'a> {
+    context: &'a Context,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        Coverage { context }
+    }
+
+    pub fn coverage_dir(&self) -> PathBuf {
+        let mut path = self.context.project_path.clone();
+        path.push(COVERAGE_DIR);
+        path
+    }
+
+    fn trace_dir(&self, contract: &Contract) -> PathBuf {
+        let mut path = self.coverage_dir();
+        path.push(&contract.name);
+        path
+    }
+
+    /// remove traces of the previous run
+    pub fn reset(&self) -> Result<()> {
+        let dir = self.coverage_dir();
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        for contract in &self.context.config.contracts {
+            fs::create_dir_all(self.trace_dir(contract))?;
+        }
+        Ok(())
+    }
+
+    /// generate lcov reports from collected traces
+    pub fn generate_reports(&self, signal: &Signal) -> Result<Vec<CoverageSummary>> {
+        let mut summaries = Vec::new();
+        for contract in &self.context.config.contracts {
+            let hits = self.load_traces(contract)?;
+            if hits.is_empty() {
+                println!("No coverage traces found for contract {}", contract.name);
+                continue;
+            }
+            let recipe = get_recipe(self.context.clone(), contract.template_type)?;
+            let symbol_path = recipe.debug_symbol_path(contract, BuildEnv::Debug);
+            let line_table = LineTable::load(self.context, symbol_path, signal)?;
+            summaries.push(self.write_lcov(contract, &line_table, &hits)?);
+        }
+        if summaries.is_empty() {
+            return Err(anyhow!(
+                "no coverage traces found in {:?}, tests must verify transactions with `verify_tx` of the tests crate",
+                self.coverage_dir()
+            ));
+        }
+        Ok(summaries)
+    }
+
+    /// generate HTML reports with `genhtml`
+    pub fn generate_html(&self, summaries: &[CoverageSummary], signal: &Signal) -> Result<()> {
+        for summary in summaries {
+            let cmd = format!(
+                "genhtml --quiet --output-directory {name} {name}.{ext}",
+                name = summary.name,
+                ext = LCOV_EXT
+            );
+            cli::run(cmd, self.coverage_dir(), signal)?;
+        }
+        Ok(())
+    }
+
+    /// count executed PCs
+    fn load_traces(&self, contract: &Contract) -> Result<HashMap<u64, u64>> {
+        let mut hits = HashMap::new();
+        let dir = self.trace_dir(contract);
+        if !dir.exists() {
+            return Ok(hits);
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(TRACE_EXT) {
+                continue;
+            }
+            for line in fs::read_to_string(&path)?.lines() {
+                let mut fields = line.split_whitespace();
+                let pc = match fields.next() {
+                    Some(pc) => pc,
+                    None => continue,
+                };
+                let pc = u64::from_str_radix(pc.trim_start_matches("0x"), 16)
+                    .map_err(|err| anyhow!("invalid pc '{}' in {:?}: {}", line, path, err))?;
+                let count = match fields.next() {
+                    Some(count) => count.parse::<u64>().map_err(|err| {
+                        anyhow!("invalid count '{}' in {:?}: {}", line, path, err)
+                    })?,
+                    None => 1,
+                };
+                *hits.entry(pc).or_insert(0) += count;
+            }
+        }
+        Ok(hits)
+    }
+
+    fn write_lcov(
+        &self,
+        contract: &Contract,
+        line_table: &LineTable,
+        hits: &HashMap<u64, u64>,
+    ) -> Result<CoverageSummary> {
+        // file -> line -> hits
+        let mut lines: BTreeMap<&str, BTreeMap<u32, u64>> = BTreeMap::new();
+        for row in line_table.rows() {
+            lines
+                .entry(row.file.as_str())
+                .or_default()
+                .entry(row.line)
+                .or_insert(0);
+        }
+        for (pc, count) in hits {
+            if let Some(row) = line_table.lookup(*pc) {
+                *lines
+                    .entry(row.file.as_str())
+                    .or_default()
+                    .entry(row.line)
+                    .or_insert(0) += count;
+            }
+        }
+
+        let mut report_path = self.coverage_dir();
+        report_path.push(format!("{}.{}", contract.name, LCOV_EXT));
+        let mut f = fs::File::create(&report_path)?;
+        let mut lines_found = 0;
+        let mut lines_hit = 0;
+        writeln!(f, "TN:{}", contract.name)?;
+        for (file, file_lines) in &lines {
+            writeln!(f, "SF:{}", file)?;
+            for (line, count) in file_lines {
+                writeln!(f, "DA:{},{}", line, count)?;
+            }
+            let found = file_lines.len();
+            let hit = file_lines.values().filter(|count| **count > 0).count();
+            writeln!(f, "LF:{}", found)?;
+            writeln!(f, "LH:{}", hit)?;
+            writeln!(f, "end_of_record")?;
+            lines_found += found;
+            lines_hit += hit;
+        }
+        Ok(CoverageSummary {
+            name: contract.name.clone(),
+            lines_found,
+            lines_hit,
+            report_path,
+        })
+    }
+}
@@ -0,0 +1,105 @@
+//! Editor integration via Debug Adapter Protocol
+//!
+//! Generate a `.vscode/launch.json` configuration for the `cppdbg` adapter,
+//! which attaches to the GDB server started by `capsule debugger start --dap`.
+
+use crate::config::Contract;
+use crate::project_context::{BuildEnv, Context};
+use crate::recipe::get_recipe;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+const VSCODE_DIR: &str = ".vscode";
+const LAUNCH_FILE: &str = "launch.json";
+const GDB_BIN: &str = "riscv64-unknown-elf-gdb";
+// code dir of the recipe is mapped to /code in the build image
+const CONTAINER_CODE_DIR: &str = "/code";
+
+/// dir relative to the project as a VS Code path
+fn workspace_path(dir: &str) -> String {
+    if dir.is_empty() {
+        "${workspaceFolder}".to_string()
+    } else {
+        format!("${{workspaceFolder}}/{}", dir)
+    }
+}
+
+fn launch_config(
+    name: &str,
+    program: &str,
+    contract_dir: &str,
+    code_dir: &str,
+    listen_port: usize,
+) -> Value {
+    json!({
+        "name": name,
+        "type": "cppdbg",
+        "request": "launch",
+        "program": workspace_path(program),
+        "cwd": workspace_path(contract_dir),
+        "MIMode": "gdb",
+        "miDebuggerPath": GDB_BIN,
+        "miDebuggerServerAddress": format!("127.0.0.1:{}", listen_port),
+        "stopAtEntry": true,
+        "sourceFileMap": {
+            CONTAINER_CODE_DIR: workspace_path(code_dir),
+        },
+    })
+}
+
+/// Write or update the debugging configuration of the contract,
+/// other configurations in `launch.json` are kept.
+pub fn write_launch_config(
+    context: &Context,
+    contract: &Contract,
+    env: BuildEnv,
+    listen_port: usize,
+) -> Result<PathBuf> {
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    let symbol_path = recipe.debug_symbol_path(contract, env);
+    let program = symbol_path
+        .strip_prefix(&context.project_path)
+        .map_err(|_| anyhow!("unstripped binary must be under the project dir"))?
+        .to_str()
+        .expect("path")
+        .to_string();
+    let contract_dir = match recipe.source_paths(contract).first() {
+        Some(path) if path.is_dir() => path.as_path(),
+        Some(path) => path.parent().expect("source dir"),
+        None => context.project_path.as_path(),
+    }
+    .strip_prefix(&context.project_path)
+    .map_err(|_| anyhow!("contract sources must be under the project dir"))?
+    .to_str()
+    .expect("path")
+    .to_string();
+    let code_dir = recipe
+        .code_dir()
+        .strip_prefix(&context.project_path)
+        .map_err(|_| anyhow!("code dir must be under the project dir"))?
+        .to_str()
+        .expect("path")
+        .to_string();
+    let name = format!("capsule: {}", contract.name);
+    let config = launch_config(&name, &program, &contract_dir, &code_dir, listen_port);
+
+    let mut launch_path = context.project_path.clone();
+    launch_path.push(VSCODE_DIR);
+    fs::create_dir_all(&launch_path)?;
+    launch_path.push(LAUNCH_FILE);
+    let mut launch: Value = if launch_path.exists() {
+        serde_json::from_str(&fs::read_to_string(&launch_path)?)
+            .map_err(|err| anyhow!("failed to parse {:?}: {}", launch_path, err))?
+    } else {
+        json!({ "version": "0.2.0", "configurations": [] })
+    };
+    let configurations = launch["configurations"]
+        .as_array_mut()
+        .ok_or(anyhow!("no 'configurations' in {:?}", launch_path))?;
+    configurations.retain(|c| c["name"] != json!(name));
+    configurations.push(config);
+    fs::write(&launch_path, serde_json::to_string_pretty(&launch)?)?;
+    Ok(launch_path)
+}
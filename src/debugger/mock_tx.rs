@@ -0,0 +1,200 @@
+//! Build debugging templates from on-chain transactions
+
+use anyhow::{anyhow, Result};
+use ckb_tool::ckb_hash::blake2b_256;
+use ckb_tool::ckb_jsonrpc_types::{
+    CellDep, CellOutput, DepType, HeaderView, JsonBytes, OutPoint, Transaction,
+};
+use ckb_tool::ckb_types::{packed, prelude::*, H256};
+use ckb_tool::rpc_client::RpcClient;
+use serde_json::{json, Value};
+
+pub struct MockTxBuilder {
+    rpc_client: RpcClient,
+}
+
+impl MockTxBuilder {
+    pub fn new(uri: &str) -> Self {
+        MockTxBuilder {
+            rpc_client: RpcClient::new(uri),
+        }
+    }
+
+    /// load a committed transaction
+    pub fn load_transaction(&self, tx_hash: H256) -> Result<Transaction> {
+        self.load_transaction_with_block(tx_hash).map(|(tx, _)| tx)
+    }
+
+    /// load a transaction and the hash of the block which contains it
+    fn load_transaction_with_block(&self, tx_hash: H256) -> Result<(Transaction, Option<H256>)> {
+        let tx = self
+            .rpc_client
+            .inner()
+            .get_transaction(tx_hash.clone())
+            .map_err(|err| anyhow!("rpc get_transaction error: {:?}", err))?
+            .ok_or_else(|| anyhow!("can't find transaction {:#x}", tx_hash))?;
+        Ok((tx.transaction.inner, tx.tx_status.block_hash))
+    }
+
+    fn load_cell(&self, out_point: &OutPoint) -> Result<MockCell> {
+        let (tx, block_hash) = self.load_transaction_with_block(out_point.tx_hash.clone())?;
+        let index = out_point.index.value() as usize;
+        let output = tx.outputs.get(index).cloned();
+        let data = tx.outputs_data.get(index).cloned();
+        match (output, data) {
+            (Some(output), Some(data)) => Ok(MockCell {
+                output,
+                data,
+                block_hash,
+            }),
+            _ => Err(anyhow!(
+                "can't find cell {:#x}#{}",
+                out_point.tx_hash,
+                index
+            )),
+        }
+    }
+
+    fn load_header(&self, block_hash: H256) -> Result<HeaderView> {
+        self.rpc_client
+            .inner()
+            .get_header(block_hash.clone())
+            .map_err(|err| anyhow!("rpc get_header error: {:?}", err))?
+            .ok_or_else(|| anyhow!("can't find header {:#x}", block_hash))
+    }
+
+    /// Resolve inputs, cell deps (dep groups are expanded) and header deps into a debugging template.
+    /// Data of the `replace` cell is replaced with the contract data mark.
+    pub fn build_template(
+        &self,
+        tx: Transaction,
+        replace: Option<(OutPoint, String)>,
+    ) -> Result<String> {
+        let mut patcher = CellPatcher {
+            replace,
+            replaced_code_hash: None,
+            header_deps: tx.header_deps.clone(),
+        };
+
+        let mut inputs = Vec::new();
+        for input in &tx.inputs {
+            let mock_cell = self.load_cell(&input.previous_output)?;
+            let mut cell = patcher.mock_cell(&input.previous_output, mock_cell);
+            cell["input"] = json!(input);
+            inputs.push(cell);
+        }
+
+        let mut cell_deps = Vec::new();
+        for cell_dep in &tx.cell_deps {
+            let mock_cell = self.load_cell(&cell_dep.out_point)?;
+            if cell_dep.dep_type == DepType::DepGroup {
+                let out_points = packed::OutPointVec::from_slice(mock_cell.data.as_bytes())
+                    .map_err(|err| anyhow!("invalid dep group data: {}", err))?;
+                for out_point in out_points.into_iter() {
+                    let out_point: OutPoint = out_point.into();
+                    let mock_cell = self.load_cell(&out_point)?;
+                    let mut cell = patcher.mock_cell(&out_point, mock_cell);
+                    cell["cell_dep"] = json!(CellDep {
+                        out_point,
+                        dep_type: DepType::Code,
+                    });
+                    cell_deps.push(cell);
+                }
+            }
+            let mut cell = patcher.mock_cell(&cell_dep.out_point, mock_cell);
+            cell["cell_dep"] = json!(cell_dep);
+            cell_deps.push(cell);
+        }
+
+        let mut header_deps = Vec::new();
+        for block_hash in &tx.header_deps {
+            header_deps.push(self.load_header(block_hash.clone())?);
+        }
+
+        let template = json!({
+            "mock_info": {
+                "inputs": inputs,
+                "cell_deps": cell_deps,
+                "header_deps": header_deps,
+            },
+            "tx": tx,
+        });
+        let mut content = serde_json::to_string_pretty(&template)?;
+        // scripts referencing the replaced cell by data hash
+        match (patcher.replace, patcher.replaced_code_hash) {
+            (_, Some((code_hash, contract))) => {
+                content = content.replace(&code_hash, &format!("{{{{{}.code_hash}}}}", contract));
+            }
+            (Some((out_point, _)), None) => {
+                return Err(anyhow!(
+                    "out point {:#x}#{} is not referenced by the transaction",
+                    out_point.tx_hash,
+                    out_point.index.value()
+                ));
+            }
+            (None, None) => {}
+        }
+        Ok(content)
+    }
+}
+
+/// a resolved cell and the block which contains it
+struct MockCell {
+    output: CellOutput,
+    data: JsonBytes,
+    block_hash: Option<H256>,
+}
+
+/// Replace the deployed cell data with the contract data mark
+struct CellPatcher {
+    replace: Option<(OutPoint, String)>,
+    // (data hash, contract name)
+    replaced_code_hash: Option<(String, String)>,
+    header_deps: Vec<H256>,
+}
+
+impl CellPatcher {
+    fn mock_cell(&mut self, out_point: &OutPoint, cell: MockCell) -> Value {
+        let MockCell {
+            output,
+            data,
+            block_hash,
+        } = cell;
+        // scripts can only load headers of cells whose block is in header deps
+        let header = block_hash.filter(|hash| self.header_deps.contains(hash));
+        match self.replace.as_ref() {
+            Some((target, contract)) if target == out_point => {
+                let data_hash = blake2b_256(data.as_bytes());
+                self.replaced_code_hash = Some((
+                    format!("0x{}", faster_hex::hex_string(&data_hash).expect("hex")),
+                    contract.clone(),
+                ));
+                json!({
+                    "output": output,
+                    "data": format!("{{{{{}.data}}}}", contract),
+                    "header": header,
+                })
+            }
+            _ => json!({
+                "output": output,
+                "data": data,
+                "header": header,
+            }),
+        }
+    }
+}
+
+/// parse transaction from a json file,
+/// accepts a transaction or a `send_transaction` request
+pub fn parse_transaction(content: &str) -> Result<Transaction> {
+    let mut value: Value = serde_json::from_str(content)?;
+    // send_transaction request: { "params": [tx, ...] }
+    if let Some(tx) = value.get_mut("params").and_then(|params| params.get_mut(0)) {
+        value = tx.take();
+    }
+    // get_transaction response: { "transaction": { ... } }
+    if let Some(tx) = value.get_mut("transaction") {
+        value = tx.take();
+    }
+    serde_json::from_value(value).map_err(|err| anyhow!("invalid transaction: {}", err))
+}
@@ -0,0 +1,234 @@
+pub mod dap;
+pub mod mock_tx;
+pub mod profile;
+pub mod syscall;
+pub mod template;
+pub mod trace;
+
+pub use template::{build_template, check_template, patch_template};
+
+use crate::project_context::{BuildEnv, Context};
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::util::docker::DockerCommand;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+const TMP_DIR: &str = ".tmp";
+const DEBUGGER_LOG_FILE: &str = "debugger.log";
+const CONTAINER_TEMPLATE_PATH: &str = "/tmp/tx.json";
+
+/// Result of a script group execution
+#[derive(Debug, Serialize)]
+pub struct RunResult {
+    pub contract: String,
+    pub exit_code: Option<i8>,
+    pub error: Option<String>,
+    pub cycles: Option<u64>,
+    pub debug_output: Vec<String>,
+}
+
+impl RunResult {
+    /// parse ckb-debugger output
+    fn parse(contract: String, output: &str) -> Self {
+        let mut result = RunResult {
+            contract,
+            exit_code: None,
+            error: None,
+            cycles: None,
+            debug_output: Vec::new(),
+        };
+        for line in output.lines() {
+            if let Some(index) = line.find("DEBUG OUTPUT:") {
+                let msg = line[index + "DEBUG OUTPUT:".len()..].trim();
+                result.debug_output.push(msg.to_string());
+            } else if let Some(run_result) = line.strip_prefix("Run result:") {
+                let run_result = run_result.trim();
+                match run_result
+                    .strip_prefix("Ok(")
+                    .and_then(|code| code.strip_suffix(")"))
+                    .and_then(|code| code.parse().ok())
+                {
+                    Some(code) => result.exit_code = Some(code),
+                    None => result.error = Some(run_result.to_string()),
+                }
+            } else if let Some(cycles) = line.strip_prefix("Total cycles consumed:") {
+                result.cycles = cycles
+                    .split_whitespace()
+                    .next()
+                    .and_then(|c| c.parse().ok());
+            } else if line.starts_with("Error") {
+                result.error = Some(line.to_string());
+            }
+        }
+        result
+    }
+}
+
+/// patch template into the project's tmp dir, returns the patched template path
+fn prepare_template<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    env: BuildEnv,
+) -> Result<String> {
+    let project_path = context
+        .project_path
+        .to_str()
+        .expect("project path")
+        .to_string();
+    let template_file_path = template_path
+        .as_ref()
+        .file_name()
+        .expect("not a file")
+        .to_str()
+        .expect("file name");
+    let patched_template_dir = format!("{}/{}", project_path, TMP_DIR);
+    let patched_template_path = format!("{}/{}", patched_template_dir, template_file_path);
+    fs::create_dir_all(patched_template_dir)?;
+    let template_path = template_path
+        .as_ref()
+        .to_str()
+        .expect("template path")
+        .to_string();
+    patch_template(context, env, &template_path, &patched_template_path)?;
+    Ok(patched_template_path)
+}
+
+/// Run a script group with ckb-debugger
+pub fn run_script<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    contract_name: &str,
+    env: BuildEnv,
+    script_group_type: &str,
+    cell_index: usize,
+    cell_type: &str,
+    max_cycles: u64,
+    signal: &Signal,
+) -> Result<RunResult> {
+    let project_path = context
+        .project_path
+        .to_str()
+        .expect("project path")
+        .to_string();
+    let patched_template_path = prepare_template(context, template_path, env)?;
+    // the script may fail, always keep the output
+    let cmd = format!(
+        "ckb-debugger --script-group-type {} --cell-index {} --cell-type {} --tx-file {} --max-cycle {} > /code/{tmp}/{log} 2>&1 || true",
+        script_group_type, cell_index, cell_type, CONTAINER_TEMPLATE_PATH, max_cycles, tmp = TMP_DIR, log = DEBUGGER_LOG_FILE
+    );
+    DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path.clone())
+        .map_volume(patched_template_path, CONTAINER_TEMPLATE_PATH.to_string())
+        .fix_dir_permission(TMP_DIR.to_string())
+        .run(cmd, signal)?;
+    let log_path = format!("{}/{}/{}", project_path, TMP_DIR, DEBUGGER_LOG_FILE);
+    let output = fs::read_to_string(&log_path)?;
+    fs::remove_file(&log_path)?;
+    print!("{}", output);
+    Ok(RunResult::parse(contract_name.to_string(), &output))
+}
+
+pub fn start_debugger<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    contract_name: &str,
+    env: BuildEnv,
+    script_group_type: &str,
+    cell_index: usize,
+    cell_type: &str,
+    max_cycles: u64,
+    listen_port: usize,
+    tty: bool,
+    signal: &Signal,
+) -> Result<()> {
+    const DEBUG_SERVER_NAME: &str = "capsule-debugger-server";
+
+    let project_path = context
+        .project_path
+        .to_str()
+        .expect("project path")
+        .to_string();
+    let patched_template_path = prepare_template(context, template_path, env)?;
+
+    // start GDB server container
+    let cmd = format!(
+        "ckb-debugger --script-group-type {} --cell-index {} --cell-type {} --tx-file {} --max-cycle {} --listen 127.0.0.1:{}",
+        script_group_type, cell_index, cell_type, CONTAINER_TEMPLATE_PATH, max_cycles, listen_port
+    );
+    println!("GDB server is started!");
+    DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path.clone())
+        .host_network(true)
+        .name(DEBUG_SERVER_NAME.to_string())
+        .daemon(tty)
+        .map_volume(patched_template_path, CONTAINER_TEMPLATE_PATH.to_string())
+        .run(cmd, signal)?;
+    if tty {
+        let contract_path = match env {
+            BuildEnv::Debug => format!("build/debug/{}", contract_name),
+            BuildEnv::Release => format!("build/release/{}", contract_name),
+        };
+        // start gdb client
+        let cmd = format!(
+            "RUST_GDB=riscv64-unknown-elf-gdb rust-gdb -ex 'target remote :{port}' -ex 'file {contract_path}' -ex 'cd contracts/{contract}'",
+            port=listen_port,
+            contract=contract_name,
+            contract_path=contract_path
+        );
+        let docker_cmd =
+            DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path)
+                .host_network(true)
+                .tty(true);
+
+        // Prepare a specific docker environment for GDB client then enable this
+        //
+        // if let Some(mut home_dir) = dirs::home_dir() {
+        //     home_dir.push(".gdbinit");
+        //     if home_dir.exists() {
+        //         let host = home_dir.to_str().expect("path").to_string();
+        //         docker_cmd = docker_cmd.map_volume(host, "/root/.gdbinit".to_string());
+        //     }
+        // }
+
+        docker_cmd.run(cmd, signal)?;
+    }
+    DockerCommand::stop(DEBUG_SERVER_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_run_result() {
+        let output = "script group: Byte32(0x0a3e) DEBUG OUTPUT: hello\n\
+                      script group: Byte32(0x0a3e) DEBUG OUTPUT: args: 0x\n\
+                      Run result: Ok(0)\n\
+                      Total cycles consumed: 4593\n";
+        let result = RunResult::parse("my-lock".to_string(), output);
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(result.error, None);
+        assert_eq!(result.cycles, Some(4593));
+        assert_eq!(result.debug_output, vec!["hello", "args: 0x"]);
+
+        let output = "script group: Byte32(0x0a3e) DEBUG OUTPUT: invalid args\n\
+                      Run result: Ok(-5)\n\
+                      Total cycles consumed: 1021\n";
+        let result = RunResult::parse("my-lock".to_string(), output);
+        assert_eq!(result.exit_code, Some(-5));
+        assert_eq!(result.debug_output, vec!["invalid args"]);
+
+        let output = "Run result: Err(Error { kind: ExceededMaximumCycles })\n";
+        let result = RunResult::parse("my-lock".to_string(), output);
+        assert_eq!(result.exit_code, None);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("Err(Error { kind: ExceededMaximumCycles })")
+        );
+        assert_eq!(result.cycles, None);
+
+        let result = RunResult::parse("my-lock".to_string(), "Error: can't open tx file\n");
+        assert_eq!(result.error.as_deref(), Some("Error: can't open tx file"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Script profiling
+//!
+//! ckb-debugger runs the unstripped copy of the binary, samples PCs and symbolizes them,
+//! the output is folded stacks (`main;foo;bar <cycles>`), which is used to generate
+//! flamegraph and the per-function cycles table.
+
+use super::{prepare_template, CONTAINER_TEMPLATE_PATH};
+use crate::config::Contract;
+use crate::project_context::{BuildEnv, Context};
+use crate::recipe::get_recipe;
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::util::cli;
+use crate::util::docker::DockerCommand;
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROFILE_DIR: &str = "build/profile";
+const FLAMEGRAPH_BIN: &str = "inferno-flamegraph";
+
+/// Cycles of a function
+#[derive(Debug, Clone)]
+pub struct FunctionCycles {
+    pub name: String,
+    pub self_cycles: u64,
+    pub total_cycles: u64,
+}
+
+pub struct Profile {
+    pub folded_path: PathBuf,
+    pub svg_path: Option<PathBuf>,
+    pub total_cycles: u64,
+    pub functions: Vec<FunctionCycles>,
+}
+
+impl Profile {
+    /// print functions sorted by self cycles
+    pub fn print_table(&self, limit: usize) {
+        println!("------------------------------");
+        println!("function\tself cycles\ttotal cycles\tself %");
+        for f in self.functions.iter().take(limit) {
+            let percent = if self.total_cycles == 0 {
+                0.0
+            } else {
+                f.self_cycles as f64 * 100.0 / self.total_cycles as f64
+            };
+            println!(
+                "{}\t{}\t{}\t{:.2}%",
+                f.name, f.self_cycles, f.total_cycles, percent
+            );
+        }
+        println!("------------------------------");
+        println!("total cycles: {}", self.total_cycles);
+        println!("folded stacks: {:?}", self.folded_path);
+        if let Some(svg_path) = self.svg_path.as_ref() {
+            println!("flamegraph: {:?}", svg_path);
+        }
+    }
+}
+
+/// Profile a script group
+pub fn profile_script<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    contract: &Contract,
+    env: BuildEnv,
+    script_group_type: &str,
+    cell_index: usize,
+    cell_type: &str,
+    max_cycles: u64,
+    flamegraph: bool,
+    signal: &Signal,
+) -> Result<Profile> {
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    // symbols must come from the profiled code, the stripped binary is replaced by its unstripped copy
+    let bin_path = recipe.unstripped_binary_path(contract, env).ok_or_else(|| {
+        anyhow!(
+            "release binary of contract '{}' is stripped, profiling needs symbols of the profiled code\nhint: build an unstripped release binary (without `-C link-arg=-s`), or profile the debug build without `--release`",
+            contract.name
+        )
+    })?;
+    if !bin_path.exists() {
+        return Err(anyhow!(
+            "can't find unstripped binary of contract '{}' at {:?}\nhint: build it first, Makefile rules of old projects must copy the binary to `$@.unstripped` before stripping",
+            contract.name,
+            bin_path
+        ));
+    }
+    let rel_bin_path = bin_path
+        .strip_prefix(&context.project_path)
+        .map_err(|_| anyhow!("unstripped binary must be under the project dir"))?
+        .to_path_buf();
+
+    let project_path = context
+        .project_path
+        .to_str()
+        .expect("project path")
+        .to_string();
+    let patched_template_path = prepare_template(context, template_path, env)?;
+    let mut profile_dir = context.project_path.clone();
+    profile_dir.push(PROFILE_DIR);
+    fs::create_dir_all(&profile_dir)?;
+    let folded_name = format!("{}.folded", contract.name);
+    let mut folded_path = profile_dir.clone();
+    folded_path.push(&folded_name);
+    // a stale file of the previous run would be reported if ckb-debugger fails
+    if folded_path.exists() {
+        fs::remove_file(&folded_path)?;
+    }
+    // the script binary is replaced by the unstripped one, stacks are written into the pprof file
+    let cmd = format!(
+        "ckb-debugger --script-group-type {} --cell-index {} --cell-type {} --tx-file {} --max-cycle {} --bin {} --pprof {}/{}",
+        script_group_type,
+        cell_index,
+        cell_type,
+        CONTAINER_TEMPLATE_PATH,
+        max_cycles,
+        rel_bin_path.to_str().expect("path"),
+        PROFILE_DIR,
+        folded_name
+    );
+    DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path)
+        .map_volume(patched_template_path, CONTAINER_TEMPLATE_PATH.to_string())
+        .fix_dir_permission(PROFILE_DIR.to_string())
+        .run(cmd, signal)?;
+
+    let folded = fs::read_to_string(&folded_path)
+        .map_err(|err| anyhow!("can't read folded stacks {:?}: {}", folded_path, err))?;
+    let (total_cycles, functions) = parse_folded_stacks(&folded);
+
+    let svg_path = if flamegraph {
+        let svg_name = format!("{}.svg", contract.name);
+        let cmd = format!(
+            "{} --title '{}' {} > {}",
+            FLAMEGRAPH_BIN, contract.name, folded_name, svg_name
+        );
+        cli::run(cmd, &profile_dir, signal)?;
+        let mut svg_path = profile_dir;
+        svg_path.push(svg_name);
+        Some(svg_path)
+    } else {
+        None
+    };
+
+    Ok(Profile {
+        folded_path,
+        svg_path,
+        total_cycles,
+        functions,
+    })
+}
+
+/// a folded stack line `main;foo;bar <cycles>`, other lines are ignored
+fn parse_folded_line(line: &str) -> Option<(Vec<&str>, u64)> {
+    let line = line.trim();
+    let index = line.rfind(' ')?;
+    let cycles: u64 = line[index + 1..].parse().ok()?;
+    let stack = line[..index].trim();
+    // messages of ckb-debugger, e.g. `Total cycles consumed: 45`
+    if stack.is_empty() || stack.ends_with(':') || stack.contains(": ") {
+        return None;
+    }
+    Some((stack.split(';').collect(), cycles))
+}
+
+/// returns total cycles and functions sorted by self cycles
+fn parse_folded_stacks(content: &str) -> (u64, Vec<FunctionCycles>) {
+    let mut total_cycles = 0;
+    let mut functions: HashMap<&str, FunctionCycles> = HashMap::new();
+    for (frames, cycles) in content.lines().filter_map(parse_folded_line) {
+        total_cycles += cycles;
+        // recursive functions are counted once
+        let mut seen = HashSet::new();
+        for (i, frame) in frames.iter().enumerate() {
+            let frame = *frame;
+            let f = functions.entry(frame).or_insert_with(|| FunctionCycles {
+                name: frame.to_string(),
+                self_cycles: 0,
+                total_cycles: 0,
+            });
+            if seen.insert(frame) {
+                f.total_cycles += cycles;
+            }
+            if i == frames.len() - 1 {
+                f.self_cycles += cycles;
+            }
+        }
+    }
+    let mut functions: Vec<_> = functions.into_iter().map(|(_, f)| f).collect();
+    functions.sort_by(|a, b| b.self_cycles.cmp(&a.self_cycles));
+    (total_cycles, functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_folded_stacks() {
+        let content =
+            "Run result: 0\nmain;foo 30\nmain;foo;foo 10\nmain 5\nTotal cycles consumed: 45\n";
+        let (total_cycles, functions) = parse_folded_stacks(content);
+        assert_eq!(total_cycles, 45);
+        let foo = functions.iter().find(|f| f.name == "foo").unwrap();
+        assert_eq!((foo.self_cycles, foo.total_cycles), (40, 40));
+        let main = functions.iter().find(|f| f.name == "main").unwrap();
+        assert_eq!((main.self_cycles, main.total_cycles), (5, 45));
+    }
+}
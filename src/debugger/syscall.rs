@@ -0,0 +1,421 @@
+//! Syscall log of script execution
+//!
+//! Describe `ckb_load_*` syscalls recorded by the trace recorder,
+//! cells are annotated with the names found from template marks.
+//! Group sources are mapped to transaction indices through the script group being run.
+
+use super::trace::{Event, Trace};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+const SOURCE_GROUP_FLAG: u64 = 0x0100_0000_0000_0000;
+
+/// Cell names of a debugging template
+///
+/// A cell is named after the contract in its data mark `{{contract.data}}`,
+/// or the contracts in the code hash marks of its lock and type scripts.
+#[derive(Default)]
+pub struct CellNames {
+    inputs: Vec<Option<String>>,
+    outputs: Vec<Option<String>>,
+    cell_deps: Vec<Option<String>>,
+    /// transaction indices of inputs and outputs in the script group
+    group_inputs: Vec<usize>,
+    group_outputs: Vec<usize>,
+}
+
+/// lock and type scripts of cells
+fn cell_scripts(cells: &Value, output_key: Option<&str>) -> Vec<(Value, Value)> {
+    cells
+        .as_array()
+        .map(|cells| {
+            cells
+                .iter()
+                .map(|cell| {
+                    let output = match output_key {
+                        Some(key) => &cell[key],
+                        None => cell,
+                    };
+                    (output["lock"].clone(), output["type"].clone())
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl CellNames {
+    /// names of the template, the script group is the one passed to ckb-debugger
+    pub fn from_template<P: AsRef<Path>>(
+        template_path: P,
+        script_group_type: &str,
+        cell_index: usize,
+        cell_type: &str,
+    ) -> Result<Self> {
+        let template_path = template_path.as_ref();
+        let template: Value = serde_json::from_str(&fs::read_to_string(template_path)?)
+            .map_err(|err| anyhow!("failed to parse {:?}: {}", template_path, err))?;
+        Ok(Self::from_value(
+            &template,
+            script_group_type,
+            cell_index,
+            cell_type,
+        ))
+    }
+
+    fn from_value(
+        template: &Value,
+        script_group_type: &str,
+        cell_index: usize,
+        cell_type: &str,
+    ) -> Self {
+        let names = |cells: &Value, output_key: Option<&str>| -> Vec<Option<String>> {
+            cells
+                .as_array()
+                .map(|cells| {
+                    cells
+                        .iter()
+                        .map(|cell| {
+                            let output = match output_key {
+                                Some(key) => &cell[key],
+                                None => cell,
+                            };
+                            cell_name(output, &cell["data"])
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let mut outputs = names(&template["tx"]["outputs"], None);
+        // outputs data are in a separated list
+        if let Some(outputs_data) = template["tx"]["outputs_data"].as_array() {
+            for (name, data) in outputs.iter_mut().zip(outputs_data) {
+                if let Some(contract) = data.as_str().and_then(|s| mark_contract(s, "data")) {
+                    *name = Some(contract);
+                }
+            }
+        }
+
+        // a lock group contains inputs, a type group contains inputs and outputs
+        let input_scripts = cell_scripts(&template["mock_info"]["inputs"], Some("output"));
+        let output_scripts = cell_scripts(&template["tx"]["outputs"], None);
+        let is_lock = script_group_type == "lock";
+        let group_script = |(lock, type_): &(Value, Value)| -> Value {
+            if is_lock {
+                lock.clone()
+            } else {
+                type_.clone()
+            }
+        };
+        let cells = if cell_type == "output" {
+            &output_scripts
+        } else {
+            &input_scripts
+        };
+        let script = cells
+            .get(cell_index)
+            .map(group_script)
+            .filter(|script| !script.is_null());
+        let group = |cells: &[(Value, Value)]| -> Vec<usize> {
+            cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| script.as_ref() == Some(&group_script(cell)))
+                .map(|(i, _)| i)
+                .collect()
+        };
+        let group_inputs = group(&input_scripts);
+        let group_outputs = if is_lock {
+            Vec::new()
+        } else {
+            group(&output_scripts)
+        };
+
+        CellNames {
+            inputs: names(&template["mock_info"]["inputs"], Some("output")),
+            outputs,
+            cell_deps: names(&template["mock_info"]["cell_deps"], Some("output")),
+            group_inputs,
+            group_outputs,
+        }
+    }
+
+    /// transaction source and index of a cell, group sources are mapped through the script group
+    fn resolve(&self, source: u64, index: u64) -> Option<(u64, u64)> {
+        if source & SOURCE_GROUP_FLAG == 0 {
+            return Some((source, index));
+        }
+        let source = source & !SOURCE_GROUP_FLAG;
+        let group = match source {
+            1 => &self.group_inputs,
+            2 => &self.group_outputs,
+            _ => return None,
+        };
+        group
+            .get(index as usize)
+            .map(|tx_index| (source, *tx_index as u64))
+    }
+
+    fn name(&self, source: u64, index: u64) -> Option<&str> {
+        let cells = match source {
+            1 => &self.inputs,
+            2 => &self.outputs,
+            3 => &self.cell_deps,
+            _ => return None,
+        };
+        cells
+            .get(index as usize)
+            .and_then(|name| name.as_ref())
+            .map(|name| name.as_str())
+    }
+}
+
+fn cell_name(output: &Value, data: &Value) -> Option<String> {
+    if let Some(contract) = data.as_str().and_then(|s| mark_contract(s, "data")) {
+        return Some(contract);
+    }
+    let script_contract = |script: &Value| {
+        script["code_hash"]
+            .as_str()
+            .and_then(|s| mark_contract(s, "code_hash"))
+    };
+    match (
+        script_contract(&output["lock"]),
+        script_contract(&output["type"]),
+    ) {
+        (Some(lock), Some(type_)) => Some(format!("lock: {}, type: {}", lock, type_)),
+        (Some(lock), None) => Some(format!("lock: {}", lock)),
+        (None, Some(type_)) => Some(format!("type: {}", type_)),
+        (None, None) => None,
+    }
+}
+
+/// contract of the `{{contract.attribute}}` mark
+fn mark_contract(s: &str, attribute: &str) -> Option<String> {
+    s.trim()
+        .strip_prefix("{{")?
+        .strip_suffix("}}")?
+        .trim()
+        .strip_suffix(attribute)?
+        .strip_suffix('.')
+        .map(|contract| contract.to_string())
+}
+
+fn source_name(source: u64) -> String {
+    let name = match source & !SOURCE_GROUP_FLAG {
+        1 => "input",
+        2 => "output",
+        3 => "cell_dep",
+        4 => "header_dep",
+        _ => return format!("unknown source {:#x}", source),
+    };
+    if source & SOURCE_GROUP_FLAG != 0 {
+        format!("group_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn field_name(number: u64, field: u64) -> String {
+    let fields: &[&str] = match number {
+        2081 => &[
+            "capacity",
+            "data_hash",
+            "lock",
+            "lock_hash",
+            "type",
+            "type_hash",
+            "occupied_capacity",
+        ],
+        2082 => &["epoch_number", "epoch_start_block_number", "epoch_length"],
+        2083 => &["out_point", "since"],
+        _ => &[],
+    };
+    fields
+        .get(field as usize)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("unknown field {}", field))
+}
+
+fn syscall_name(number: u64) -> Option<&'static str> {
+    let name = match number {
+        2051 => "load_transaction",
+        2052 => "load_script",
+        2061 => "load_tx_hash",
+        2062 => "load_script_hash",
+        2071 => "load_cell",
+        2072 => "load_header",
+        2073 => "load_input",
+        2074 => "load_witness",
+        2081 => "load_cell_by_field",
+        2082 => "load_header_by_field",
+        2083 => "load_input_by_field",
+        2091 => "load_cell_data_as_code",
+        2092 => "load_cell_data",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn error_name(code: u64) -> String {
+    match code {
+        0 => "success".to_string(),
+        1 => "index out of bound".to_string(),
+        2 => "item missing".to_string(),
+        3 => "slice out of bound".to_string(),
+        4 => "wrong format".to_string(),
+        _ => format!("error {}", code),
+    }
+}
+
+/// Describe `ckb_load_*` syscalls, one line per syscall
+pub fn describe_syscalls(trace: &Trace, names: &CellNames) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (number, args, ret) in trace.syscalls() {
+        let name = match syscall_name(number) {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut line = name.to_string();
+        // load_cell_data_as_code(addr, memory_size, content_offset, content_size, index, source)
+        let (offset, cell) = if number == 2091 {
+            (args[2], Some((args[4], args[5])))
+        } else if number >= 2071 {
+            (args[2], Some((args[3], args[4])))
+        } else {
+            (args[2], None)
+        };
+        if let Some((index, source)) = cell {
+            line.push_str(&format!(" source={} index={}", source_name(source), index));
+        }
+        match cell {
+            // witnesses are not cells, only the witness index is mapped
+            Some((index, source)) if number == 2074 => {
+                if let Some((tx_source, tx_index)) = names.resolve(source, index) {
+                    if tx_source != source {
+                        line.push_str(&format!(" (witness {})", tx_index));
+                    }
+                }
+            }
+            Some((index, source)) => {
+                if let Some((tx_source, tx_index)) = names.resolve(source, index) {
+                    if tx_source != source {
+                        line.push_str(&format!(" ({} {})", source_name(tx_source), tx_index));
+                    }
+                    if let Some(cell_name) = names.name(tx_source, tx_index) {
+                        line.push_str(&format!(" ({})", cell_name));
+                    }
+                }
+            }
+            None => {}
+        }
+        if (2081..=2083).contains(&number) {
+            line.push_str(&format!(" field={}", field_name(number, args[5])));
+        }
+        line.push_str(&format!(" offset={}", offset));
+        match ret {
+            Some(Event::Return {
+                value: 0,
+                length: Some(length),
+                ..
+            }) => line.push_str(&format!(" => length {}", length)),
+            Some(Event::Return { value, .. }) => {
+                line.push_str(&format!(" => {}", error_name(*value)))
+            }
+            _ => line.push_str(" => no return"),
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::trace::Step;
+    use serde_json::json;
+
+    fn cell(lock: &str, type_: Option<&str>) -> Value {
+        let script =
+            |code_hash: &str| json!({"code_hash": code_hash, "hash_type": "data", "args": "0x"});
+        json!({
+            "lock": script(lock),
+            "type": type_.map(script),
+        })
+    }
+
+    fn names() -> CellNames {
+        let template = json!({
+            "mock_info": {
+                "inputs": [
+                    {"output": cell("{{lock-a.code_hash}}", None), "data": "0x"},
+                    {"output": cell("{{lock-b.code_hash}}", Some("{{my-type.code_hash}}")), "data": "0x"},
+                    {"output": cell("{{lock-a.code_hash}}", Some("{{my-type.code_hash}}")), "data": "0x"},
+                ],
+                "cell_deps": [
+                    {"output": cell("0x00", None), "data": "{{my-type.data}}"},
+                ],
+            },
+            "tx": {
+                "outputs": [
+                    cell("{{lock-b.code_hash}}", None),
+                    cell("{{lock-a.code_hash}}", Some("{{my-type.code_hash}}")),
+                ],
+                "outputs_data": ["0x", "0x"],
+            },
+        });
+        CellNames::from_value(&template, "type", 1, "input")
+    }
+
+    #[test]
+    fn test_resolve() {
+        let names = names();
+        // group input 1 is the input 2, group output 0 is the output 1
+        assert_eq!(names.resolve(SOURCE_GROUP_FLAG | 1, 1), Some((1, 2)));
+        assert_eq!(names.resolve(SOURCE_GROUP_FLAG | 2, 0), Some((2, 1)));
+        assert_eq!(names.resolve(SOURCE_GROUP_FLAG | 2, 1), None);
+        assert_eq!(names.resolve(3, 0), Some((3, 0)));
+        assert_eq!(names.name(3, 0), Some("my-type"));
+        assert_eq!(names.name(1, 0), Some("lock: lock-a"));
+
+        // a lock group has no outputs
+        let lock_names = CellNames {
+            group_outputs: Vec::new(),
+            ..CellNames::default()
+        };
+        assert_eq!(lock_names.resolve(SOURCE_GROUP_FLAG | 2, 0), None);
+    }
+
+    #[test]
+    fn test_describe_syscalls() {
+        let syscall = |number: u64, args: [u64; 6], length: u64| Step {
+            pc: 0,
+            events: vec![
+                Event::Syscall { number, args },
+                Event::Return {
+                    value: 0,
+                    length: Some(length),
+                    data: Vec::new(),
+                },
+            ],
+        };
+        let trace = Trace {
+            steps: vec![
+                syscall(2092, [0, 0, 0, 0, SOURCE_GROUP_FLAG | 1, 0], 10),
+                syscall(2074, [0, 0, 0, 1, SOURCE_GROUP_FLAG | 1, 0], 20),
+                syscall(2081, [0, 0, 0, 0, 3, 1], 32),
+                syscall(2061, [0, 0, 0, 0, 0, 0], 32),
+            ],
+        };
+        assert_eq!(
+            describe_syscalls(&trace, &names()),
+            vec![
+                "load_cell_data source=group_input index=0 (input 1) (lock: lock-b, type: my-type) offset=0 => length 10",
+                "load_witness source=group_input index=1 (witness 2) offset=0 => length 20",
+                "load_cell_by_field source=cell_dep index=0 (my-type) field=data_hash offset=0 => length 32",
+                "load_tx_hash offset=0 => length 32",
+            ]
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Debugging template and template marks
+//!
+//! Marks are surrounded by `{{` and `}}`:
+//!
+//! * `{{contract.attribute}}` - `data`, `code_hash` (or `data_hash`) and `capacity` of a built contract
+//! * `{{function(arg, ...)}}` - see `resolve_function`
+
+use crate::deployment::manage::load_latest_recipe;
+use crate::deployment::recipe::CellRecipe;
+use crate::generator::TEMPLATES;
+use crate::project_context::{BuildEnv, Context, DeployEnv};
+use anyhow::{anyhow, Result};
+use ckb_tool::ckb_hash::blake2b_256;
+use ckb_tool::ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType},
+    packed,
+    prelude::*,
+};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use tera::{self, Context as TeraContext};
+
+// capacity(8) + lock code_hash(32) + lock hash_type(1) + lock args(20)
+const SECP256K1_CELL_SIZE: usize = 61;
+
+#[derive(Serialize)]
+struct TemplateContext {
+    name: String,
+}
+
+pub fn build_template(contract_name: String) -> Result<String> {
+    let context = TeraContext::from_serialize(&TemplateContext {
+        name: contract_name,
+    })?;
+    let content = TEMPLATES.render("debugger/template.json", &context)?;
+    Ok(content)
+}
+
+#[derive(Debug, PartialEq)]
+enum Mark {
+    Attribute { contract: String, attribute: String },
+    Function { name: String, args: Vec<String> },
+}
+
+impl Mark {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(index) = s.find('(') {
+            if !s.ends_with(')') {
+                return Err(anyhow!("expect ')' at the end of '{}'", s));
+            }
+            let name = s[..index].trim().to_string();
+            let args_str = s[index + 1..s.len() - 1].trim();
+            let args = if args_str.is_empty() {
+                Vec::new()
+            } else {
+                args_str
+                    .split(',')
+                    .map(|arg| arg.trim().to_string())
+                    .collect()
+            };
+            return Ok(Mark::Function { name, args });
+        }
+        // contract name may contain '.', e.g. 'lib.so.data'
+        let mut parts = s.rsplitn(2, '.');
+        match (parts.next(), parts.next()) {
+            (Some(attribute), Some(contract)) if !contract.is_empty() => Ok(Mark::Attribute {
+                contract: contract.to_string(),
+                attribute: attribute.to_string(),
+            }),
+            _ => Err(anyhow!(
+                "syntax error, expect 'contract.attribute' or 'function(args)'"
+            )),
+        }
+    }
+}
+
+struct MarkResolver<'a> {
+    context: &'a Context,
+    env: BuildEnv,
+}
+
+impl<'a> MarkResolver<'a> {
+    fn resolve(&self, mark: &Mark) -> Result<String> {
+        match mark {
+            Mark::Attribute {
+                contract,
+                attribute,
+            } => self.resolve_attribute(contract, attribute),
+            Mark::Function { name, args } => self.resolve_function(name, args),
+        }
+    }
+
+    fn load_contract(&self, contract: &str) -> Result<Vec<u8>> {
+        let mut path = self.context.contracts_build_path(self.env);
+        path.push(contract);
+        if !path.exists() {
+            return Err(anyhow!("contract not exists: {:?}", path));
+        }
+        Ok(fs::read(path)?)
+    }
+
+    fn resolve_attribute(&self, contract: &str, attribute: &str) -> Result<String> {
+        let bin = self.load_contract(contract)?;
+        match attribute {
+            "data" => hex(&bin),
+            "code_hash" | "data_hash" => hex(&blake2b_256(&bin)),
+            "capacity" => capacity(bin.len()),
+            _ => Err(anyhow!("unknown attribute '{}'", attribute)),
+        }
+    }
+
+    /// deployed cell in the last migration
+    fn deployed_cell(&self, contract: &str, env: &str) -> Result<CellRecipe> {
+        let env: DeployEnv = env.parse().map_err(|err: &str| anyhow!(err))?;
+        let recipe = load_latest_recipe(&self.context.migrations_path(env))?
+            .ok_or(anyhow!("no migrations found"))?;
+        recipe
+            .cell_recipes
+            .into_iter()
+            .find(|cell| cell.name == contract)
+            .ok_or(anyhow!("cell '{}' is not deployed", contract))
+    }
+
+    fn resolve_function(&self, name: &str, args: &[String]) -> Result<String> {
+        let arg = |i: usize| nth_arg(name, args, i);
+        match name {
+            // blake2b hash of a file, the path is relative to the project dir
+            "data_hash" => {
+                let mut path = self.context.project_path.clone();
+                path.push(arg(0)?);
+                let data =
+                    fs::read(&path).map_err(|err| anyhow!("can't read {:?}: {}", path, err))?;
+                hex(&blake2b_256(&data))
+            }
+            // hash of the script which uses contract as code with data hash type
+            "script_hash" => {
+                let bin = self.load_contract(arg(0)?)?;
+                let args = decode_hex(args.get(1).map(|s| s.as_str()).unwrap_or("0x"))?;
+                let script = packed::Script::new_builder()
+                    .code_hash(blake2b_256(&bin).pack())
+                    .hash_type(ScriptHashType::Data.into())
+                    .args(Bytes::from(args).pack())
+                    .build();
+                hex(script.calc_script_hash().as_slice())
+            }
+            // capacity of a secp256k1 locked cell with data size
+            "capacity" => capacity(arg(0)?.parse()?),
+            "type_id" => {
+                let cell = self.deployed_cell(arg(0)?, arg(1)?)?;
+                let type_id = cell
+                    .type_id
+                    .ok_or(anyhow!("cell '{}' is not deployed with type id", cell.name))?;
+                hex(type_id.as_bytes())
+            }
+            "tx_hash" => hex(self.deployed_cell(arg(0)?, arg(1)?)?.tx_hash.as_bytes()),
+            "index" => Ok(format!(
+                "{:#x}",
+                self.deployed_cell(arg(0)?, arg(1)?)?.index
+            )),
+            // molecule values
+            "uint8" => hex(&[arg(0)?.parse::<u8>()?]),
+            "uint32" => hex(arg(0)?.parse::<u32>()?.pack().as_slice()),
+            "uint64" => hex(arg(0)?.parse::<u64>()?.pack().as_slice()),
+            "uint128" => hex(arg(0)?.parse::<u128>()?.pack().as_slice()),
+            "bytes" => hex(Bytes::from(decode_hex(arg(0)?)?).pack().as_slice()),
+            "byte32" => {
+                let data = decode_hex(arg(0)?)?;
+                if data.len() != 32 {
+                    return Err(anyhow!("expect 32 bytes, got {}", data.len()));
+                }
+                hex(&data)
+            }
+            _ => Err(anyhow!("unknown function '{}'", name)),
+        }
+    }
+}
+
+fn nth_arg<'a>(name: &str, args: &'a [String], i: usize) -> Result<&'a str> {
+    args.get(i).map(|arg| arg.as_str()).ok_or(anyhow!(
+        "'{}' expects at least {} arguments",
+        name,
+        i + 1
+    ))
+}
+
+fn hex(data: &[u8]) -> Result<String> {
+    Ok(format!("0x{}", faster_hex::hex_string(data)?))
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    let s = s.trim_start_matches("0x");
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    if s.len() % 2 != 0 {
+        return Err(anyhow!("invalid hex '{}': odd length", s));
+    }
+    let mut buf = vec![0u8; s.len() / 2];
+    faster_hex::hex_decode(s.as_bytes(), &mut buf)
+        .map_err(|err| anyhow!("invalid hex '{}': {}", s, err))?;
+    Ok(buf)
+}
+
+fn capacity(data_size: usize) -> Result<String> {
+    let capacity = Capacity::bytes(SECP256K1_CELL_SIZE + data_size)
+        .map_err(|err| anyhow!("capacity overflow: {:?}", err))?;
+    Ok(format!("{:#x}", capacity.as_u64()))
+}
+
+/// search marks from template
+fn search_marks(template: &str) -> Result<BTreeSet<String>> {
+    let left: Vec<_> = template.match_indices("{{").collect();
+    let right: Vec<_> = template.match_indices("}}").collect();
+    if left.len() != right.len() {
+        return Err(anyhow!(
+            "Has {} '{{{{', but {} '}}}}'",
+            left.len(),
+            right.len()
+        ));
+    }
+
+    let mut marks = BTreeSet::new();
+    for ((start, _), (end, _)) in left.into_iter().zip(right) {
+        if start > end {
+            return Err(anyhow!("'}}}}' at {} has no begin mark", end));
+        }
+        marks.insert(template[(start + 2)..end].to_string());
+    }
+    Ok(marks)
+}
+
+/// resolve all marks, returns the patches and errors of unresolved marks
+fn resolve_marks(
+    context: &Context,
+    env: BuildEnv,
+    template: &str,
+) -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
+    let resolver = MarkResolver { context, env };
+    let mut patches = Vec::new();
+    let mut errors = Vec::new();
+    for mark_str in search_marks(template)? {
+        match Mark::parse(&mark_str).and_then(|mark| resolver.resolve(&mark)) {
+            Ok(patch) => patches.push((mark_str, patch)),
+            Err(err) => errors.push((mark_str, err.to_string())),
+        }
+    }
+    Ok((patches, errors))
+}
+
+/// Validate template, returns all unresolved marks and the reasons
+pub fn check_template<P: AsRef<Path>>(
+    context: &Context,
+    env: BuildEnv,
+    src: P,
+) -> Result<Vec<(String, String)>> {
+    let template = fs::read_to_string(src)?;
+    let (_patches, errors) = resolve_marks(context, env, &template)?;
+    Ok(errors)
+}
+
+/// patch template
+pub fn patch_template<P: AsRef<Path>>(
+    context: &Context,
+    env: BuildEnv,
+    src: P,
+    dst: P,
+) -> Result<()> {
+    let mut template = fs::read_to_string(src)?;
+
+    // 1. resolve marks from src
+    let (patches, errors) = resolve_marks(context, env, &template)?;
+    if !errors.is_empty() {
+        let msg: Vec<_> = errors
+            .iter()
+            .map(|(mark, err)| format!("'{{{{{}}}}}': {}", mark, err))
+            .collect();
+        return Err(anyhow!(
+            "failed to resolve template marks:\n{}",
+            msg.join("\n")
+        ));
+    }
+
+    // 2. replace template in memory
+    for (mark, patch) in patches {
+        let source = format!("{{{{{}}}}}", mark);
+        template = template.replace(source.as_str(), patch.as_str());
+    }
+
+    // 3. dump template to dst
+    fs::write(dst, template)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mark() {
+        assert_eq!(
+            Mark::parse(" lib.so.data ").unwrap(),
+            Mark::Attribute {
+                contract: "lib.so".to_string(),
+                attribute: "data".to_string(),
+            }
+        );
+        assert_eq!(
+            Mark::parse("script_hash(my-lock, 0x01)").unwrap(),
+            Mark::Function {
+                name: "script_hash".to_string(),
+                args: vec!["my-lock".to_string(), "0x01".to_string()],
+            }
+        );
+        assert_eq!(
+            Mark::parse("bytes()").unwrap(),
+            Mark::Function {
+                name: "bytes".to_string(),
+                args: Vec::new(),
+            }
+        );
+        assert!(Mark::parse("bytes(0x00").is_err());
+        assert!(Mark::parse(".data").is_err());
+        assert!(Mark::parse("data").is_err());
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("0x").unwrap(), Vec::<u8>::new());
+        assert_eq!(decode_hex("").unwrap(), Vec::<u8>::new());
+        assert_eq!(decode_hex("0x00ff").unwrap(), vec![0x00, 0xff]);
+        assert_eq!(decode_hex("abcd").unwrap(), vec![0xab, 0xcd]);
+        assert!(decode_hex("0x123").is_err());
+        assert!(decode_hex("0xzz").is_err());
+    }
+}
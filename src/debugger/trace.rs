@@ -0,0 +1,794 @@
+//! Execution trace recording and replaying
+//!
+//! The recorder drives the ckb-debugger GDB server with a scripted GDB client,
+//! which single steps the script and logs PCs, syscalls and stores.
+//! Each instruction is a GDB round trip, recording runs at a few thousand instructions per second,
+//! so a script which consumes millions of cycles takes minutes to hours.
+//! The trace is written into `build/trace/<contract>.trace`, one event per line:
+//!
+//! * `<pc>` - an executed instruction, events below belong to it
+//! * `w <addr> <size> <value>` - a memory write
+//! * `s <number> <a0> .. <a5>` - a syscall and its arguments
+//! * `r <ret> [<length> [<data>]]` - syscall return value, the data length and the loaded data
+//! * `t <length>` - the loaded data is truncated, only the first bytes are recorded
+//! * `d <message>` - debug message
+//!
+//! All numbers are hex.
+
+use super::{prepare_template, CONTAINER_TEMPLATE_PATH, TMP_DIR};
+use crate::config::Contract;
+use crate::project_context::{BuildEnv, Context};
+use crate::recipe::get_recipe;
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::util::docker::DockerCommand;
+use crate::util::dwarf::LineTable;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const TRACE_DIR: &str = "build/trace";
+const TRACE_SERVER_NAME: &str = "capsule-trace-server";
+const GDB_BIN: &str = "riscv64-unknown-elf-gdb";
+// max bytes of loaded data recorded for a syscall, longer data is marked as truncated
+const MAX_SYSCALL_DATA: u64 = 4096;
+const SYSCALL_DEBUG: u64 = 2177;
+const SYSCALL_EXIT: u64 = 93;
+const REGISTERS: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "fp", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Write {
+        addr: u64,
+        size: u8,
+        value: u64,
+    },
+    Syscall {
+        number: u64,
+        args: [u64; 6],
+    },
+    Return {
+        value: u64,
+        length: Option<u64>,
+        data: Vec<u8>,
+    },
+    /// the data of the previous return is truncated, the length of the loaded data
+    Truncated(u64),
+    Debug(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub pc: u64,
+    pub events: Vec<Event>,
+}
+
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content =
+            fs::read_to_string(path).map_err(|err| anyhow!("can't read {:?}: {}", path, err))?;
+        Self::parse(&content).map_err(|err| anyhow!("invalid trace {:?}: {}", path, err))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut steps: Vec<Step> = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = || anyhow!("line {}: '{}'", i + 1, line);
+            let mut parts = line.splitn(2, ' ');
+            let kind = parts.next().expect("kind");
+            let rest = parts.next().unwrap_or("");
+            let event = match kind {
+                "w" => {
+                    let values = parse_hex_list(rest).ok_or_else(err)?;
+                    if values.len() != 3 {
+                        return Err(err());
+                    }
+                    Event::Write {
+                        addr: values[0],
+                        size: values[1] as u8,
+                        value: values[2],
+                    }
+                }
+                "s" => {
+                    let values = parse_hex_list(rest).ok_or_else(err)?;
+                    if values.len() != 7 {
+                        return Err(err());
+                    }
+                    let mut args = [0u64; 6];
+                    args.copy_from_slice(&values[1..]);
+                    Event::Syscall {
+                        number: values[0],
+                        args,
+                    }
+                }
+                "r" => {
+                    let mut values = rest.split_whitespace();
+                    let value = values.next().and_then(parse_hex).ok_or_else(err)?;
+                    let length = match values.next() {
+                        Some(length) => Some(parse_hex(length).ok_or_else(err)?),
+                        None => None,
+                    };
+                    let data = match values.next() {
+                        Some(data) => decode_hex(data).ok_or_else(err)?,
+                        None => Vec::new(),
+                    };
+                    Event::Return {
+                        value,
+                        length,
+                        data,
+                    }
+                }
+                "t" => Event::Truncated(parse_hex(rest).ok_or_else(err)?),
+                "d" => Event::Debug(rest.to_string()),
+                pc => {
+                    let pc = parse_hex(pc).ok_or_else(err)?;
+                    steps.push(Step {
+                        pc,
+                        events: Vec::new(),
+                    });
+                    continue;
+                }
+            };
+            steps.last_mut().ok_or_else(err)?.events.push(event);
+        }
+        Ok(Trace { steps })
+    }
+
+    /// convert GDB client log into trace
+    fn from_gdb_log(log: &str) -> Result<Self> {
+        let mut steps: Vec<Step> = Vec::new();
+        let mut syscall_data: Option<Vec<u8>> = None;
+        for line in log.lines() {
+            let line = line.trim();
+            // data dumped by `x/<n>xb`, e.g. `0x11de0 <buf>:\t0x01\t0x02`
+            if line.starts_with("0x") {
+                if let (Some(data), Some(index)) = (syscall_data.as_mut(), line.find(':')) {
+                    for byte in line[index + 1..].split_whitespace() {
+                        data.push(parse_hex(byte).ok_or(anyhow!("invalid data '{}'", line))? as u8);
+                    }
+                }
+                continue;
+            }
+            flush_syscall_data(&mut steps, &mut syscall_data);
+            let mut parts = line.splitn(2, ' ');
+            let kind = parts.next().unwrap_or("");
+            let rest = parts.next().unwrap_or("");
+            let event = match kind {
+                "P" => {
+                    let pc = parse_hex(rest).ok_or(anyhow!("invalid pc '{}'", line))?;
+                    steps.push(Step {
+                        pc,
+                        events: Vec::new(),
+                    });
+                    continue;
+                }
+                "W" => {
+                    let values = parse_hex_list(rest).ok_or(anyhow!("invalid store '{}'", line))?;
+                    if values.len() != 33 {
+                        return Err(anyhow!("invalid store '{}'", line));
+                    }
+                    match decode_store(values[0] as u32, &values[1..]) {
+                        Some((addr, size, value)) => Event::Write { addr, size, value },
+                        None => continue,
+                    }
+                }
+                "S" => {
+                    let values =
+                        parse_hex_list(rest).ok_or(anyhow!("invalid syscall '{}'", line))?;
+                    if values.len() != 7 {
+                        return Err(anyhow!("invalid syscall '{}'", line));
+                    }
+                    let mut args = [0u64; 6];
+                    args.copy_from_slice(&values[1..]);
+                    Event::Syscall {
+                        number: values[0],
+                        args,
+                    }
+                }
+                "R" => {
+                    let values =
+                        parse_hex_list(rest).ok_or(anyhow!("invalid return '{}'", line))?;
+                    if values.is_empty() {
+                        return Err(anyhow!("invalid return '{}'", line));
+                    }
+                    syscall_data = Some(Vec::new());
+                    Event::Return {
+                        value: values[0],
+                        length: values.get(1).cloned(),
+                        data: Vec::new(),
+                    }
+                }
+                "T" => Event::Truncated(
+                    parse_hex(rest).ok_or(anyhow!("invalid truncated data '{}'", line))?,
+                ),
+                "D" => Event::Debug(rest.to_string()),
+                // GDB messages
+                _ => continue,
+            };
+            match steps.last_mut() {
+                Some(step) => step.events.push(event),
+                None => return Err(anyhow!("event before the first instruction: '{}'", line)),
+            }
+        }
+        flush_syscall_data(&mut steps, &mut syscall_data);
+        Ok(Trace { steps })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut f = io::BufWriter::new(fs::File::create(path)?);
+        for step in &self.steps {
+            writeln!(f, "{:x}", step.pc)?;
+            for event in &step.events {
+                match event {
+                    Event::Write { addr, size, value } => {
+                        writeln!(f, "w {:x} {:x} {:x}", addr, size, value)?
+                    }
+                    Event::Syscall { number, args } => {
+                        let args: Vec<_> = args.iter().map(|arg| format!("{:x}", arg)).collect();
+                        writeln!(f, "s {:x} {}", number, args.join(" "))?
+                    }
+                    Event::Return {
+                        value,
+                        length: None,
+                        ..
+                    } => writeln!(f, "r {:x}", value)?,
+                    Event::Return {
+                        value,
+                        length: Some(length),
+                        data,
+                    } if data.is_empty() => writeln!(f, "r {:x} {:x}", value, length)?,
+                    Event::Return {
+                        value,
+                        length: Some(length),
+                        data,
+                    } => writeln!(
+                        f,
+                        "r {:x} {:x} {}",
+                        value,
+                        length,
+                        faster_hex::hex_string(data)?
+                    )?,
+                    Event::Truncated(length) => writeln!(f, "t {:x}", length)?,
+                    Event::Debug(msg) => writeln!(f, "d {}", msg)?,
+                }
+            }
+        }
+        f.flush()?;
+        Ok(())
+    }
+
+    /// syscalls with the returns
+    pub fn syscalls(&self) -> Vec<(u64, &[u64; 6], Option<&Event>)> {
+        let mut syscalls = Vec::new();
+        for step in &self.steps {
+            for (i, event) in step.events.iter().enumerate() {
+                if let Event::Syscall { number, args } = event {
+                    let ret = step.events[i + 1..]
+                        .iter()
+                        .find(|event| matches!(event, Event::Return { .. }));
+                    syscalls.push((*number, args, ret));
+                }
+            }
+        }
+        syscalls
+    }
+
+    fn is_syscall(&self, index: usize) -> bool {
+        self.steps[index]
+            .events
+            .iter()
+            .any(|event| matches!(event, Event::Syscall { .. }))
+    }
+
+    /// number of syscalls whose loaded data is truncated
+    pub fn truncated_syscalls(&self) -> usize {
+        self.steps
+            .iter()
+            .flat_map(|step| step.events.iter())
+            .filter(|event| matches!(event, Event::Truncated(_)))
+            .count()
+    }
+
+    /// exit code of the script
+    pub fn exit_code(&self) -> Option<i8> {
+        self.steps.iter().rev().find_map(|step| {
+            step.events.iter().find_map(|event| match event {
+                Event::Syscall { number, args } if *number == SYSCALL_EXIT => Some(args[0] as i8),
+                _ => None,
+            })
+        })
+    }
+}
+
+/// attach dumped data to the last syscall return
+fn flush_syscall_data(steps: &mut Vec<Step>, syscall_data: &mut Option<Vec<u8>>) {
+    if let Some(data) = syscall_data.take() {
+        if let Some(Event::Return { data: ret_data, .. }) =
+            steps.last_mut().and_then(|step| step.events.last_mut())
+        {
+            *ret_data = data;
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+fn parse_hex_list(s: &str) -> Option<Vec<u64>> {
+    s.split_whitespace().map(parse_hex).collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let mut buf = vec![0u8; s.len() / 2];
+    faster_hex::hex_decode(s.as_bytes(), &mut buf).ok()?;
+    Some(buf)
+}
+
+fn sign_extend(value: u64, bits: u32) -> u64 {
+    (((value << (64 - bits)) as i64) >> (64 - bits)) as u64
+}
+
+/// decode store instruction, returns address, size and the stored value
+fn decode_store(insn: u32, regs: &[u64]) -> Option<(u64, u8, u64)> {
+    let (rs1, rs2, offset, size) = if insn & 0x3 == 0x3 {
+        // sb, sh, sw, sd
+        if insn & 0x7f != 0x23 {
+            return None;
+        }
+        let imm = ((insn >> 25) << 5) | ((insn >> 7) & 0x1f);
+        let size = 1u8 << ((insn >> 12) & 0x7).min(3);
+        (
+            (insn >> 15) & 0x1f,
+            (insn >> 20) & 0x1f,
+            sign_extend(imm as u64, 12),
+            size,
+        )
+    } else {
+        let c = insn & 0xffff;
+        match c & 0xe003 {
+            // c.sw
+            0xc000 => (
+                8 + ((c >> 7) & 0x7),
+                8 + ((c >> 2) & 0x7),
+                (((c >> 10) & 0x7) << 3 | ((c >> 6) & 0x1) << 2 | ((c >> 5) & 0x1) << 6) as u64,
+                4,
+            ),
+            // c.sd
+            0xe000 => (
+                8 + ((c >> 7) & 0x7),
+                8 + ((c >> 2) & 0x7),
+                (((c >> 10) & 0x7) << 3 | ((c >> 5) & 0x3) << 6) as u64,
+                8,
+            ),
+            // c.swsp
+            0xc002 => (
+                2,
+                (c >> 2) & 0x1f,
+                (((c >> 9) & 0xf) << 2 | ((c >> 7) & 0x3) << 6) as u64,
+                4,
+            ),
+            // c.sdsp
+            0xe002 => (
+                2,
+                (c >> 2) & 0x1f,
+                (((c >> 10) & 0x7) << 3 | ((c >> 7) & 0x7) << 6) as u64,
+                8,
+            ),
+            _ => return None,
+        }
+    };
+    let addr = regs[rs1 as usize].wrapping_add(offset);
+    let value = match size {
+        8 => regs[rs2 as usize],
+        _ => regs[rs2 as usize] & ((1u64 << (size as u32 * 8)) - 1),
+    };
+    Some((addr, size, value))
+}
+
+/// GDB script which single steps the script until exit,
+/// only PCs of syscalls are logged in the syscalls only mode
+fn gdb_script(
+    listen_port: usize,
+    symbol_path: Option<&str>,
+    log_path: &str,
+    syscalls_only: bool,
+) -> String {
+    let regs: Vec<_> = REGISTERS.iter().map(|r| format!("${}", r)).collect();
+    let file = match symbol_path {
+        Some(path) => format!("file {}", path),
+        None => String::new(),
+    };
+    let (step_log, store_log, syscall_log) = if syscalls_only {
+        ("", String::new(), "printf \"P %lx\\n\", $pc")
+    } else {
+        (
+            "printf \"P %lx\\n\", $pc",
+            format!(
+                "if ($insn & 0x7f) == 0x23 || ($insn & 0xe003) == 0xc000 || ($insn & 0xe003) == 0xe000 || ($insn & 0xe003) == 0xc002 || ($insn & 0xe003) == 0xe002\n    printf \"W %x{}\\n\", $insn, {}\n  end",
+                " %lx".repeat(REGISTERS.len()),
+                regs.join(", ")
+            ),
+            "",
+        )
+    };
+    format!(
+        r#"set pagination off
+set confirm off
+set height 0
+set width 0
+target remote 127.0.0.1:{port}
+{file}
+set logging file {log}
+set logging overwrite on
+set logging redirect on
+set logging on
+while 1
+  set $insn = *(unsigned int *)$pc
+  {step_log}
+  {store_log}
+  if $insn == 0x73
+    {syscall_log}
+    printf "S %lx %lx %lx %lx %lx %lx %lx\n", $a7, $a0, $a1, $a2, $a3, $a4, $a5
+    if $a7 == {debug}
+      printf "D %s\n", (char *)$a0
+    end
+    set $sc_load = $a7 >= 2051 && $a7 <= 2092 && $a7 != 2091 && $a1 != 0
+    set $sc_addr = $a0
+    set $sc_len_ptr = $a1
+    if $sc_load
+      set $sc_buf_len = *(unsigned long *)$a1
+    end
+    stepi
+    if $sc_load && $a0 == 0
+      set $sc_size = *(unsigned long *)$sc_len_ptr
+      printf "R %lx %lx\n", $a0, $sc_size
+      if $sc_size > $sc_buf_len
+        set $sc_size = $sc_buf_len
+      end
+      set $sc_truncated = 0
+      if $sc_size > {max_data}
+        set $sc_truncated = $sc_size
+        set $sc_size = {max_data}
+      end
+      if $sc_size > 0
+        eval "x/%luxb %lu", $sc_size, $sc_addr
+      end
+      if $sc_truncated
+        printf "T %lx\n", $sc_truncated
+      end
+    else
+      printf "R %lx\n", $a0
+    end
+  else
+    stepi
+  end
+end
+"#,
+        port = listen_port,
+        file = file,
+        log = log_path,
+        step_log = step_log,
+        store_log = store_log,
+        syscall_log = syscall_log,
+        debug = SYSCALL_DEBUG,
+        max_data = MAX_SYSCALL_DATA,
+    )
+}
+
+/// run the script under the scripted GDB client
+fn record<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    name: &str,
+    symbol_path: Option<&str>,
+    env: BuildEnv,
+    script_group_type: &str,
+    cell_index: usize,
+    cell_type: &str,
+    max_cycles: u64,
+    listen_port: usize,
+    syscalls_only: bool,
+    signal: &Signal,
+) -> Result<Trace> {
+    let project_path = context
+        .project_path
+        .to_str()
+        .expect("project path")
+        .to_string();
+    let patched_template_path = prepare_template(context, template_path, env)?;
+
+    let script_name = format!("{}.gdb", name);
+    let log_name = format!("{}.gdb.log", name);
+    let mut tmp_dir = context.project_path.clone();
+    tmp_dir.push(TMP_DIR);
+    fs::write(
+        tmp_dir.join(&script_name),
+        gdb_script(
+            listen_port,
+            symbol_path,
+            &format!("{}/{}", TMP_DIR, log_name),
+            syscalls_only,
+        ),
+    )?;
+
+    // start GDB server container
+    let cmd = format!(
+        "ckb-debugger --script-group-type {} --cell-index {} --cell-type {} --tx-file {} --max-cycle {} --listen 127.0.0.1:{}",
+        script_group_type, cell_index, cell_type, CONTAINER_TEMPLATE_PATH, max_cycles, listen_port
+    );
+    DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path.clone())
+        .host_network(true)
+        .name(TRACE_SERVER_NAME.to_string())
+        .daemon(true)
+        .map_volume(patched_template_path, CONTAINER_TEMPLATE_PATH.to_string())
+        .run(cmd, signal)?;
+    // GDB exits with error once the script exits and the server closes the connection
+    let cmd = format!(
+        "sleep 1 && {gdb} -batch -x {tmp}/{script} || true",
+        gdb = GDB_BIN,
+        tmp = TMP_DIR,
+        script = script_name
+    );
+    let result = DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path)
+        .host_network(true)
+        .fix_dir_permission(TMP_DIR.to_string())
+        .run(cmd, signal);
+    DockerCommand::stop(TRACE_SERVER_NAME)?;
+    result?;
+
+    let log_path = tmp_dir.join(&log_name);
+    let log = fs::read_to_string(&log_path)
+        .map_err(|err| anyhow!("can't read GDB log {:?}: {}", log_path, err))?;
+    let trace = Trace::from_gdb_log(&log)?;
+    fs::remove_file(&log_path)?;
+    fs::remove_file(tmp_dir.join(&script_name))?;
+    Ok(trace)
+}
+
+/// Record execution trace of a script group, returns the trace path
+pub fn record_trace<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    contract: &Contract,
+    env: BuildEnv,
+    script_group_type: &str,
+    cell_index: usize,
+    cell_type: &str,
+    max_cycles: u64,
+    listen_port: usize,
+    signal: &Signal,
+) -> Result<PathBuf> {
+    let recipe = get_recipe(context.clone(), contract.template_type)?;
+    let symbol_path = recipe.debug_symbol_path(contract, env);
+    if !symbol_path.exists() {
+        return Err(anyhow!(
+            "can't find unstripped binary of contract '{}' at {:?}, please build it first",
+            contract.name,
+            symbol_path
+        ));
+    }
+    let rel_symbol_path = symbol_path
+        .strip_prefix(&context.project_path)
+        .map_err(|_| anyhow!("unstripped binary must be under the project dir"))?
+        .to_str()
+        .expect("path");
+    println!(
+        "Recording trace of contract {} by single stepping under GDB, it may take minutes for large scripts...",
+        contract.name
+    );
+    let trace = record(
+        context,
+        template_path,
+        &contract.name,
+        Some(rel_symbol_path),
+        env,
+        script_group_type,
+        cell_index,
+        cell_type,
+        max_cycles,
+        listen_port,
+        false,
+        signal,
+    )?;
+
+    let mut trace_path = context.project_path.clone();
+    trace_path.push(TRACE_DIR);
+    fs::create_dir_all(&trace_path)?;
+    trace_path.push(format!("{}.trace", contract.name));
+    trace.write(&trace_path)?;
+    println!(
+        "Recorded {} instructions, exit code: {:?}",
+        trace.steps.len(),
+        trace.exit_code()
+    );
+    let truncated = trace.truncated_syscalls();
+    if truncated > 0 {
+        println!(
+            "Warning: data of {} syscalls is longer than {} bytes, it is truncated in the trace",
+            truncated, MAX_SYSCALL_DATA
+        );
+    }
+    Ok(trace_path)
+}
+
+/// Record syscalls of a script group, symbols are not required
+pub fn record_syscalls<P: AsRef<Path>>(
+    context: &Context,
+    template_path: P,
+    contract_name: &str,
+    env: BuildEnv,
+    script_group_type: &str,
+    cell_index: usize,
+    cell_type: &str,
+    max_cycles: u64,
+    listen_port: usize,
+    signal: &Signal,
+) -> Result<Trace> {
+    record(
+        context,
+        template_path,
+        contract_name,
+        None,
+        env,
+        script_group_type,
+        cell_index,
+        cell_type,
+        max_cycles,
+        listen_port,
+        true,
+        signal,
+    )
+}
+
+/// Interactive trace viewer
+pub struct Replayer {
+    trace: Trace,
+    line_table: LineTable,
+    code_dir: PathBuf,
+    source_dir: PathBuf,
+    index: usize,
+}
+
+impl Replayer {
+    pub fn new(
+        context: &Context,
+        trace: Trace,
+        contract: &Contract,
+        env: BuildEnv,
+        signal: &Signal,
+    ) -> Result<Self> {
+        if trace.steps.is_empty() {
+            return Err(anyhow!("trace is empty"));
+        }
+        let recipe = get_recipe(context.clone(), contract.template_type)?;
+        let line_table = LineTable::load(context, recipe.debug_symbol_path(contract, env), signal)?;
+        let source_dir = match recipe.source_paths(contract).first() {
+            Some(path) if path.is_dir() => path.clone(),
+            Some(path) => path.parent().expect("source dir").to_path_buf(),
+            None => context.project_path.clone(),
+        };
+        Ok(Replayer {
+            trace,
+            line_table,
+            code_dir: recipe.code_dir(),
+            source_dir,
+            index: 0,
+        })
+    }
+
+    /// sources are compiled under /code in the build image, which is the code dir of the recipe
+    fn source_line(&self, file: &str, line: u32) -> Option<String> {
+        let path = match file.strip_prefix("/code/") {
+            Some(rel_path) => self.code_dir.join(rel_path),
+            None => self.source_dir.join(file),
+        };
+        let content = fs::read_to_string(path).ok()?;
+        content
+            .lines()
+            .nth((line as usize).checked_sub(1)?)
+            .map(|s| s.trim().to_string())
+    }
+
+    fn show(&self) {
+        let step = &self.trace.steps[self.index];
+        let location = match self.line_table.lookup(step.pc) {
+            Some(row) => {
+                let source = self.source_line(&row.file, row.line).unwrap_or_default();
+                format!("{}:{}\t{}", row.file, row.line, source)
+            }
+            None => "??".to_string(),
+        };
+        println!(
+            "[{}/{}] {:#x}\t{}",
+            self.index,
+            self.trace.steps.len() - 1,
+            step.pc,
+            location
+        );
+        for event in &step.events {
+            match event {
+                Event::Write { addr, size, value } => {
+                    println!("  write {:#x} ({} bytes) = {:#x}", addr, size, value)
+                }
+                Event::Syscall { number, args } => {
+                    println!("  syscall {} args {:x?}", number, args)
+                }
+                Event::Return { value, data, .. } => {
+                    println!("  return {:#x}, {} bytes loaded", value, data.len());
+                    if !data.is_empty() {
+                        println!("  data 0x{}", faster_hex::hex_string(data).expect("hex"));
+                    }
+                }
+                Event::Truncated(length) => println!("  data truncated, {} bytes loaded", length),
+                Event::Debug(msg) => println!("  debug: {}", msg),
+            }
+        }
+    }
+
+    fn seek(&mut self, forward: bool, count: usize) {
+        let last = self.trace.steps.len() - 1;
+        self.index = if forward {
+            (self.index + count).min(last)
+        } else {
+            self.index.saturating_sub(count)
+        };
+    }
+
+    /// move to the next (or previous) syscall
+    fn seek_syscall(&mut self, forward: bool) {
+        let found = if forward {
+            (self.index + 1..self.trace.steps.len()).find(|i| self.trace.is_syscall(*i))
+        } else {
+            (0..self.index).rev().find(|i| self.trace.is_syscall(*i))
+        };
+        match found {
+            Some(i) => self.index = i,
+            None => println!("no more syscalls"),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        println!("n [count]: step forward, b [count]: step back, c: next syscall, rc: previous syscall, g <index>: go to step, q: quit");
+        self.show();
+        let stdin = io::stdin();
+        print!("(replay) ");
+        io::stdout().flush()?;
+        for line in stdin.lock().lines() {
+            let line = line?;
+            let mut parts = line.split_whitespace();
+            let command = parts.next().unwrap_or("n");
+            let count: Option<usize> = parts.next().and_then(|n| n.parse().ok());
+            match command {
+                "n" => self.seek(true, count.unwrap_or(1)),
+                "b" => self.seek(false, count.unwrap_or(1)),
+                "c" => self.seek_syscall(true),
+                "rc" => self.seek_syscall(false),
+                "g" => match count {
+                    Some(i) if i < self.trace.steps.len() => self.index = i,
+                    _ => println!("expect a step index"),
+                },
+                "q" => break,
+                _ => println!("unknown command '{}'", command),
+            }
+            self.show();
+            print!("(replay) ");
+            io::stdout().flush()?;
+        }
+        Ok(())
+    }
+}
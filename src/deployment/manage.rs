@@ -1,16 +1,40 @@
 use super::{deployment_process::DeploymentProcess, plan::Plan, recipe::DeploymentRecipe};
-use crate::config::Deployment;
+use crate::config::{CellLocation, Deployment};
 use crate::util::cli::ask_for_confirm;
+use crate::util::elf::{check_binary, is_elf, validate};
 use crate::wallet::{cli_types::LiveCell, Wallet};
 use anyhow::{anyhow, Result};
 use chrono::prelude::*;
 use ckb_tool::ckb_types::core::{Capacity, TransactionView};
 use std::fs;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 const CURRENT_SNAPSHOT: &str = "current.json";
 
+/// load the last completed migration
+pub fn load_latest_recipe(migration_dir: &Path) -> Result<Option<DeploymentRecipe>> {
+    if !migration_dir.exists() {
+        return Ok(None);
+    }
+    let file_names: Vec<_> = fs::read_dir(migration_dir)?
+        .map(|d| d.map(|d| d.file_name()))
+        .collect::<Result<_, _>>()?;
+    let last_migration_file = file_names
+        .into_iter()
+        .filter(|name| name.to_str() != Some(CURRENT_SNAPSHOT))
+        .max();
+    match last_migration_file {
+        Some(file_name) => {
+            let mut path = migration_dir.to_path_buf();
+            path.push(file_name);
+            let recipe = serde_json::from_slice(&fs::read(path)?)?;
+            Ok(Some(recipe))
+        }
+        None => Ok(None),
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct DeployOption {
     pub migrate: bool,
@@ -43,6 +67,35 @@ impl Manage {
         Ok(())
     }
 
+    /// validate contract binaries of cells, other cell data is skipped
+    fn check_binaries(&self) -> Result<()> {
+        for cell in &self.deployment.cells {
+            let file = match &cell.location {
+                CellLocation::File { file } => file,
+                CellLocation::OutPoint { .. } => continue,
+            };
+            let data = fs::read(file)
+                .map_err(|err| anyhow!("failed to read cell data from '{}', err: {}", file, err))?;
+            if !is_elf(&data) {
+                continue;
+            }
+            check_binary(file, false).map_err(|err| {
+                anyhow!(
+                    "{}\ncell '{}' can't be loaded by CKB-VM, fix and rebuild the contract before deployment",
+                    err,
+                    cell.name
+                )
+            })?;
+            if !validate(&data).stripped {
+                println!(
+                    "Warning: binary of cell '{}' isn't stripped, deploy release builds to save capacity",
+                    cell.name
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// create a snapshot in migration dir
     fn snapshot_recipe(&self, recipe: &DeploymentRecipe) -> Result<PathBuf> {
         let mut path = self.migration_dir.clone();
@@ -67,27 +120,12 @@ impl Manage {
         Ok(())
     }
 
-    fn load_snapshot(&self, snapshot_name: String) -> Result<DeploymentRecipe> {
-        let mut path = self.migration_dir.clone();
-        path.push(snapshot_name);
-        let mut buf = Vec::new();
-        fs::File::open(path)?.read_to_end(&mut buf)?;
-        let recipe = serde_json::from_slice(&buf)?;
-        Ok(recipe)
-    }
-
     fn collect_migration_live_cells(&self, wallet: &Wallet) -> Result<Vec<(String, LiveCell)>> {
-        // read last migration
-        let file_names: Vec<_> = fs::read_dir(&self.migration_dir)?
-            .map(|d| d.map(|d| d.file_name()))
-            .collect::<Result<_, _>>()?;
-        let last_migration_file = file_names.into_iter().max();
         let mut cells = Vec::new();
-        if last_migration_file.is_none() {
-            return Ok(cells);
-        }
-        let last_migration_file = last_migration_file.unwrap();
-        let recipe = self.load_snapshot(last_migration_file.into_string().unwrap())?;
+        let recipe = match load_latest_recipe(&self.migration_dir)? {
+            Some(recipe) => recipe,
+            None => return Ok(cells),
+        };
 
         // query cells recipes
         for cell in recipe.cell_recipes {
@@ -127,6 +165,7 @@ impl Manage {
         }
         // check incomplete snapshot
         self.check_incomplete_snapshot()?;
+        self.check_binaries()?;
         let mut pre_inputs = Vec::new();
         let deployment = self.deployment.clone();
         if opt.migrate {
@@ -1,5 +1,5 @@
 pub mod deployment_process;
 pub mod manage;
 mod plan;
-mod recipe;
+pub mod recipe;
 mod tx_check;
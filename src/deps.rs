@@ -0,0 +1,325 @@
+//! C dependencies
+//!
+//! Dependencies are declared in `capsule.toml`:
+//!
+//! ```toml
+//! [[c_deps]]
+//! name = "ckb-c-stdlib"
+//! source = "https://github.com/nervosnetwork/ckb-c-stdlib.git"
+//! revision = "82bc1ab07572ceacd1e016488f0a1ac7725ad3c6"
+//! checksum = "0x..."
+//! # vendored = true
+//! ```
+//!
+//! and fetched into `contracts/c/deps/<name>` without git metadata.
+//! Fetched dependencies are ignored by git unless they are vendored.
+
+use crate::config::CDependency;
+use crate::config_manipulate::{set_c_dependency, Document};
+use crate::project_context::{read_config_file, write_config_file, Context, CONFIG_FILE};
+use crate::signal::Signal;
+use crate::util::{cli, git};
+use anyhow::{anyhow, Result};
+use ckb_tool::ckb_hash::new_blake2b;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEPS_DIR: &str = "contracts/c/deps";
+const GITIGNORE_FILE: &str = ".gitignore";
+const TMP_DIR: &str = ".tmp";
+const IGNORE_FILES: &[&str] = &[".git", ".gitmodules"];
+
+pub const CKB_C_STDLIB_NAME: &str = "ckb-c-stdlib";
+pub const CKB_C_STDLIB_URL: &str = "https://github.com/nervosnetwork/ckb-c-stdlib.git";
+pub const CKB_C_STDLIB_COMMIT: &str = "82bc1ab07572ceacd1e016488f0a1ac7725ad3c6";
+
+pub fn ckb_c_stdlib() -> CDependency {
+    CDependency {
+        name: CKB_C_STDLIB_NAME.to_string(),
+        source: CKB_C_STDLIB_URL.to_string(),
+        revision: Some(CKB_C_STDLIB_COMMIT.to_string()),
+        checksum: None,
+        vendored: false,
+    }
+}
+
+fn is_tarball(source: &str) -> bool {
+    source.ends_with(".tar.gz") || source.ends_with(".tgz")
+}
+
+/// blake2b hash of files under the dir, paths are included
+pub fn checksum<P: AsRef<Path>>(dir: P) -> Result<String> {
+    fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if IGNORE_FILES.contains(&name) {
+                continue;
+            }
+            if path.is_dir() {
+                collect(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    collect(dir, &mut files)?;
+    files.sort();
+    let mut hasher = new_blake2b();
+    for path in files {
+        let rel_path = path.strip_prefix(dir).expect("sub path");
+        hasher.update(rel_path.to_str().expect("path").as_bytes());
+        hasher.update(&fs::read(&path)?);
+    }
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    Ok(format!("0x{}", faster_hex::hex_string(&hash)?))
+}
+
+pub struct CDeps<'a> {
+    context: &'a Context,
+}
+
+impl<'a> CDeps<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        CDeps { context }
+    }
+
+    fn deps_dir(&self) -> PathBuf {
+        let mut path = self.context.project_path.clone();
+        path.push(DEPS_DIR);
+        path
+    }
+
+    fn dep_dir(&self, name: &str) -> PathBuf {
+        let mut path = self.deps_dir();
+        path.push(name);
+        path
+    }
+
+    fn select<'b>(&self, deps: &'b [CDependency], names: &[&str]) -> Result<Vec<&'b CDependency>> {
+        for name in names {
+            if !deps.iter().any(|dep| &dep.name == name) {
+                return Err(anyhow!(
+                    "can't find C dependency '{}' in {}",
+                    name,
+                    CONFIG_FILE
+                ));
+            }
+        }
+        Ok(deps
+            .iter()
+            .filter(|dep| names.is_empty() || names.contains(&dep.name.as_str()))
+            .collect())
+    }
+
+    /// save dependencies into capsule.toml
+    fn save(&self, deps: &[CDependency]) -> Result<()> {
+        let mut config_path = self.context.project_path.clone();
+        config_path.push(CONFIG_FILE);
+        let mut doc = read_config_file(&config_path)?.parse::<Document>()?;
+        for dep in deps {
+            set_c_dependency(&mut doc, dep)?;
+        }
+        write_config_file(&config_path, doc.to_string())
+    }
+
+    fn gitignore(&self) -> Result<Vec<String>> {
+        let mut path = self.deps_dir();
+        path.push(GITIGNORE_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.trim().trim_start_matches('/').to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+
+    fn write_gitignore(&self, names: &[String]) -> Result<()> {
+        let mut path = self.deps_dir();
+        path.push(GITIGNORE_FILE);
+        let content: String = names.iter().map(|name| format!("/{}\n", name)).collect();
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// download the dependency, returns the checksum
+    fn download(&self, dep: &CDependency, signal: &Signal) -> Result<String> {
+        let dir = self.dep_dir(&dep.name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(self.deps_dir())?;
+        println!("Fetching {} from {}", dep.name, dep.source);
+        if is_tarball(&dep.source) {
+            let mut tmp_dir = self.context.project_path.clone();
+            tmp_dir.push(TMP_DIR);
+            fs::create_dir_all(&tmp_dir)?;
+            let archive = tmp_dir.join(format!("{}.tar.gz", dep.name));
+            fs::create_dir_all(&dir)?;
+            cli::run(
+                format!(
+                    "curl -sSfL -o {archive} {url} && tar -xzf {archive} --strip-components=1 -C {dir}",
+                    archive = cli::quote(archive.to_str().expect("path")),
+                    url = cli::quote(&dep.source),
+                    dir = cli::quote(dir.to_str().expect("path"))
+                ),
+                &self.context.project_path,
+                signal,
+            )?;
+            fs::remove_file(&archive)?;
+        } else {
+            git::clone(&dep.source, &dir)?;
+            if let Some(revision) = dep.revision.as_ref() {
+                git::checkout(&dir, revision)?;
+            }
+            let mut git_dir = dir.clone();
+            git_dir.push(".git");
+            fs::remove_dir_all(git_dir)?;
+        }
+        checksum(&dir)
+    }
+
+    /// fetch missing or modified dependencies, the checksum is recorded at the first fetch
+    pub fn fetch(&self, names: &[&str], signal: &Signal) -> Result<()> {
+        let deps: Vec<_> = self
+            .select(&self.context.config.c_deps, names)?
+            .into_iter()
+            .cloned()
+            .collect();
+        self.fetch_deps(&deps, signal)
+    }
+
+    pub fn fetch_deps(&self, deps: &[CDependency], signal: &Signal) -> Result<()> {
+        let mut ignored = self.gitignore()?;
+        let mut changed = Vec::new();
+        for dep in deps {
+            let dir = self.dep_dir(&dep.name);
+            if dir.exists() {
+                let local_checksum = checksum(&dir)?;
+                match dep.checksum.as_ref() {
+                    Some(expected) if expected == &local_checksum => {
+                        println!("{} is up to date", dep.name);
+                        continue;
+                    }
+                    None => {
+                        println!("{} exists, record checksum {}", dep.name, local_checksum);
+                        let mut dep = dep.clone();
+                        dep.checksum = Some(local_checksum);
+                        changed.push(dep);
+                        continue;
+                    }
+                    Some(_) => println!("{} is modified, fetch it again", dep.name),
+                }
+            }
+            let fetched_checksum = self.download(dep, signal)?;
+            match dep.checksum.as_ref() {
+                Some(expected) if expected != &fetched_checksum => {
+                    return Err(anyhow!(
+                        "checksum mismatch of {}, expected {}, got {}",
+                        dep.name,
+                        expected,
+                        fetched_checksum
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    let mut dep = dep.clone();
+                    dep.checksum = Some(fetched_checksum);
+                    changed.push(dep);
+                }
+            }
+            if !dep.vendored && !ignored.contains(&dep.name) {
+                ignored.push(dep.name.clone());
+            }
+        }
+        self.write_gitignore(&ignored)?;
+        self.save(&changed)
+    }
+
+    /// declare a dependency in capsule.toml without fetching
+    pub fn declare(&self, dep: &CDependency) -> Result<()> {
+        self.save(&[dep.clone()])
+    }
+
+    /// update git dependencies to the remote HEAD
+    pub fn update(&self, names: &[&str], signal: &Signal) -> Result<()> {
+        let mut updated = Vec::new();
+        for dep in self.select(&self.context.config.c_deps, names)? {
+            if is_tarball(&dep.source) {
+                println!("{} is a tarball, change the source to update it", dep.name);
+                continue;
+            }
+            let revision = git::remote_head(&dep.source)?;
+            if dep.revision.as_ref() == Some(&revision) && self.dep_dir(&dep.name).exists() {
+                println!("{} is up to date", dep.name);
+                continue;
+            }
+            let mut dep = dep.clone();
+            println!(
+                "Update {} {} -> {}",
+                dep.name,
+                dep.revision.as_deref().unwrap_or("none"),
+                revision
+            );
+            dep.revision = Some(revision);
+            dep.checksum = Some(self.download(&dep, signal)?);
+            updated.push(dep);
+        }
+        self.save(&updated)
+    }
+
+    /// add a new dependency and fetch it
+    pub fn add(&self, mut dep: CDependency, signal: &Signal) -> Result<()> {
+        if self
+            .context
+            .config
+            .c_deps
+            .iter()
+            .any(|d| d.name == dep.name)
+        {
+            return Err(anyhow!("C dependency '{}' is already exists", dep.name));
+        }
+        if dep.revision.is_none() && !is_tarball(&dep.source) {
+            dep.revision = Some(git::remote_head(&dep.source)?);
+        }
+        dep.checksum = Some(self.download(&dep, signal)?);
+        let mut ignored = self.gitignore()?;
+        ignored.push(dep.name.clone());
+        self.write_gitignore(&ignored)?;
+        self.save(&[dep])
+    }
+
+    /// commit dependencies into the project, so the project can be built offline
+    pub fn vendor(&self, names: &[&str], signal: &Signal) -> Result<()> {
+        self.fetch(names, signal)?;
+        let vendored: Vec<CDependency> = self
+            .select(&self.context.config.c_deps, names)?
+            .into_iter()
+            .map(|dep| CDependency {
+                vendored: true,
+                ..dep.clone()
+            })
+            .collect();
+        let ignored: Vec<String> = self
+            .gitignore()?
+            .into_iter()
+            .filter(|name| !vendored.iter().any(|dep| &dep.name == name))
+            .collect();
+        self.write_gitignore(&ignored)?;
+        // keep vendored dependencies out of .gitignore in later fetches
+        self.save(&vendored)?;
+        for dep in vendored {
+            println!("Vendored {:?}", self.dep_dir(&dep.name));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Fuzzing contracts with random transactions
+//!
+//! Each fuzz target is a module of the tests crate, crashes are saved under
+//! `fuzz/<contract>/crashes` as debugger templates.
+
+use crate::config::Contract;
+use crate::config_manipulate::{append_cargo_dependency, Document};
+use crate::generator::TEMPLATES;
+use crate::project_context::{
+    read_config_file, write_config_file, BuildEnv, Context, CARGO_CONFIG_FILE,
+};
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::tester::{Tester, TEST_ENV_VAR};
+use crate::util::docker::DockerCommand;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+const FUZZ_DIR: &str = "fuzz";
+const FUZZ_TEMPLATE: &str = "rust/tests/src/fuzz.rs";
+const FUZZ_ITERATIONS_VAR: &str = "CAPSULE_FUZZ_ITERATIONS";
+const FUZZ_SEED_VAR: &str = "CAPSULE_FUZZ_SEED";
+const FUZZ_DEPENDENCIES: &[(&str, &str)] = &[("rand", "0.7"), ("serde_json", "1.0")];
+
+#[derive(Debug, Copy, Clone)]
+pub struct FuzzConfig {
+    pub build_env: BuildEnv,
+    pub iterations: u64,
+    pub seed: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct FuzzTarget {
+    name: String,
+}
+
+pub struct Fuzzer<'a> {
+    context: &'a Context,
+}
+
+impl<'a> Fuzzer<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        Fuzzer { context }
+    }
+
+    fn module_name(contract: &Contract) -> String {
+        format!("fuzz_{}", contract.name.replace('-', "_"))
+    }
+
+    fn target_path(&self, contract: &Contract) -> PathBuf {
+        let mut path = self.context.tests_path();
+        path.push("src");
+        path.push(format!("{}.rs", Self::module_name(contract)));
+        path
+    }
+
+    /// generate fuzz target into tests crate if it not exists
+    pub fn create_target(&self, contract: &Contract) -> Result<()> {
+        let target_path = self.target_path(contract);
+        if target_path.exists() {
+            return Ok(());
+        }
+        println!("New fuzz target {:?}", target_path);
+        let context = tera::Context::from_serialize(&FuzzTarget {
+            name: contract.name.clone(),
+        })?;
+        let content = TEMPLATES.render(FUZZ_TEMPLATE, &context)?;
+        fs::write(&target_path, content)?;
+
+        // register module
+        let mut lib_path = self.context.tests_path();
+        lib_path.push("src/lib.rs");
+        println!("Rewrite {:?}", lib_path);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(lib_path)?
+            .write_all(
+                format!("\n#[cfg(test)]\nmod {};\n", Self::module_name(contract)).as_bytes(),
+            )?;
+
+        // add dependencies
+        let mut cargo_path = self.context.tests_path();
+        cargo_path.push(CARGO_CONFIG_FILE);
+        let config_content = read_config_file(&cargo_path)?;
+        let mut doc = config_content.parse::<Document>()?;
+        let mut changed = false;
+        for (name, version) in FUZZ_DEPENDENCIES {
+            changed |= append_cargo_dependency(&mut doc, name, version)?;
+        }
+        if changed {
+            println!("Rewrite {:?}", cargo_path);
+            write_config_file(&cargo_path, doc.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// run fuzz target in build image
+    pub fn run(&self, contract: &Contract, config: FuzzConfig, signal: &Signal) -> Result<()> {
+        self.create_target(contract)?;
+        Tester::prepare_binaries(self.context, config.build_env, true, signal)?;
+        let env_arg = match config.build_env {
+            BuildEnv::Debug => "debug",
+            BuildEnv::Release => "release",
+        };
+        let mut envs = format!(
+            "{}={} {}={}",
+            TEST_ENV_VAR, env_arg, FUZZ_ITERATIONS_VAR, config.iterations
+        );
+        if let Some(seed) = config.seed {
+            envs.push_str(&format!(" {}={}", FUZZ_SEED_VAR, seed));
+        }
+        let project_path = self.context.project_path.to_str().expect("path");
+        let cmd = DockerCommand::with_context(
+            self.context,
+            DOCKER_IMAGE.to_string(),
+            project_path.to_string(),
+        )
+        .fix_dir_permission("target".to_string())
+        .fix_dir_permission("Cargo.lock".to_string())
+        .fix_dir_permission(FUZZ_DIR.to_string());
+        cmd.run(
+            format!(
+                "{envs} cargo test -p tests {module}:: -- --ignored --nocapture",
+                envs = envs,
+                module = Self::module_name(contract)
+            ),
+            signal,
+        )
+    }
+}
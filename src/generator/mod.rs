@@ -1,3 +1,4 @@
+use crate::project_context::TESTS_DIR;
 use crate::recipe::rust::DOCKER_IMAGE;
 use crate::signal::Signal;
 use crate::util::docker::DockerCommand;
@@ -10,6 +11,8 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use tera::{self, Context, Tera};
 
+pub mod template;
+
 extern crate includedir;
 extern crate phf;
 
@@ -83,15 +86,10 @@ fn gen_project_layout<P: AsRef<Path>>(name: String, project_path: P) -> Result<(
 }
 
 fn gen_project_test<P: AsRef<Path>>(name: String, project_path: P, signal: &Signal) -> Result<()> {
-    const DEFAULT_TESTS_DIR: &str = "tests";
-
     let project_path = project_path.as_ref().to_str().expect("path");
     let cmd = DockerCommand::with_config(DOCKER_IMAGE.to_string(), project_path.to_string())
-        .fix_dir_permission(DEFAULT_TESTS_DIR.to_string());
-    cmd.run(
-        format!("cargo new {} --lib --vcs none", DEFAULT_TESTS_DIR),
-        signal,
-    )?;
+        .fix_dir_permission(TESTS_DIR.to_string());
+    cmd.run(format!("cargo new {} --lib --vcs none", TESTS_DIR), signal)?;
     let project_path = {
         let mut path = PathBuf::new();
         path.push(project_path);
@@ -104,9 +102,10 @@ fn gen_project_test<P: AsRef<Path>>(name: String, project_path: P, signal: &Sign
         version: version_string(),
     })?;
     let mut tests_path = project_path;
-    tests_path.push(DEFAULT_TESTS_DIR);
+    tests_path.push(TESTS_DIR);
     for (f, template_name) in &[
         ("src/lib.rs", None),
+        ("src/coverage.rs", None),
         ("src/tests.rs", None),
         ("Cargo.toml", Some("Cargo-manifest.toml")),
     ] {
@@ -0,0 +1,248 @@
+//! User-defined project templates
+//!
+//! A template is a directory with a `capsule-template.toml` manifest:
+//!
+//! ```toml
+//! name = "my-skeleton"
+//! description = "Contract skeleton with error types and test utilities"
+//!
+//! # files are rendered with tera, target paths are templates too
+//! [[files]]
+//! source = "error.rs"
+//! target = "contracts/{{ name }}/src/error.rs"
+//!
+//! [[variables]]
+//! name = "author"
+//! description = "Author of the contracts"
+//! default = "anonymous"
+//!
+//! # commands run in the project dir after the project is created
+//! post_create = ["git add ."]
+//! ```
+//!
+//! Templates are rendered on top of the default project layout.
+//! Remote templates are cloned into `~/.capsule/templates`.
+
+use crate::signal::Signal;
+use crate::util::{cli, git};
+use anyhow::{anyhow, Context as ErrorContext, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tera::{Context, Tera};
+
+pub const TEMPLATE_MANIFEST: &str = "capsule-template.toml";
+const TEMPLATES_CACHE_DIR: &str = ".capsule/templates";
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TemplateFile {
+    pub source: PathBuf,
+    pub target: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub default: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TemplateManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub files: Vec<TemplateFile>,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    #[serde(default)]
+    pub post_create: Vec<String>,
+}
+
+pub struct ProjectTemplate {
+    pub dir: PathBuf,
+    pub manifest: TemplateManifest,
+}
+
+/// dir of cached remote templates
+pub fn templates_cache_dir() -> Result<PathBuf> {
+    let home = env::var("HOME").map_err(|_| anyhow!("can't find the home dir, HOME is not set"))?;
+    let mut path = PathBuf::from(home);
+    path.push(TEMPLATES_CACHE_DIR);
+    Ok(path)
+}
+
+/// parse `<url>#<rev>`
+fn parse_git_source(source: &str) -> (&str, Option<&str>) {
+    match source.rfind('#') {
+        Some(index) => (&source[..index], Some(&source[index + 1..])),
+        None => (source, None),
+    }
+}
+
+/// cache dir name of a git url, e.g. `github.com-org-repo`
+fn cache_name(url: &str) -> String {
+    let url = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .splitn(2, "://")
+        .last()
+        .unwrap_or_default();
+    url.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+impl ProjectTemplate {
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let manifest_path = dir.join(TEMPLATE_MANIFEST);
+        let content = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("can't read template manifest {:?}", manifest_path))?;
+        let manifest: TemplateManifest = toml::from_str(&content)
+            .map_err(|err| anyhow!("invalid template manifest {:?}: {}", manifest_path, err))?;
+        for file in &manifest.files {
+            if !dir.join(&file.source).exists() {
+                return Err(anyhow!(
+                    "can't find template file {:?} in {:?}",
+                    file.source,
+                    dir
+                ));
+            }
+        }
+        Ok(ProjectTemplate { dir, manifest })
+    }
+
+    /// clone a `<url>#<rev>` template into the cache, cached templates are updated from the origin
+    pub fn from_git(source: &str) -> Result<Self> {
+        let (url, revision) = parse_git_source(source);
+        let mut dir = templates_cache_dir()?;
+        dir.push(cache_name(url));
+        if !dir.exists() {
+            fs::create_dir_all(dir.parent().expect("parent dir"))?;
+            println!("Cloning template {}", url);
+            git::clone(url, &dir)?;
+        }
+        // the revision is fetched from the origin, a local branch of the cache may be outdated
+        git::fetch(&dir, revision.unwrap_or("HEAD"))?;
+        git::checkout(&dir, "FETCH_HEAD")?;
+        Self::load(dir)
+    }
+
+    /// variables passed to templates, `name`, `path` and `version` are built-in
+    fn variables(&self, vars: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+        let mut variables = vars.clone();
+        for var in &self.manifest.variables {
+            if variables.contains_key(&var.name) {
+                continue;
+            }
+            match var.default.as_ref() {
+                Some(default) => {
+                    variables.insert(var.name.clone(), default.clone());
+                }
+                None => {
+                    return Err(anyhow!(
+                        "template variable '{}' is required ({}), set it with `--var {}=<value>`",
+                        var.name,
+                        var.description,
+                        var.name
+                    ))
+                }
+            }
+        }
+        Ok(variables)
+    }
+
+    /// render target paths of files, targets must be relative paths inside the project
+    fn targets(&self, context: &Context) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut targets = Vec::new();
+        for file in &self.manifest.files {
+            let target = match file.target.as_ref() {
+                Some(target) => PathBuf::from(Tera::one_off(target, context, false)?),
+                None => file.source.clone(),
+            };
+            let inside_project = target.file_name().is_some()
+                && target
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if !inside_project {
+                return Err(anyhow!(
+                    "invalid target {:?} of template file {:?}, expect a relative path inside the project",
+                    target,
+                    file.source
+                ));
+            }
+            targets.push((self.dir.join(&file.source), target));
+        }
+        Ok(targets)
+    }
+
+    /// check variables and target paths, called before the project is created
+    pub fn check(&self, vars: &HashMap<String, String>) -> Result<()> {
+        let context = Context::from_serialize(&self.variables(vars)?)?;
+        self.targets(&context).map(|_| ())
+    }
+
+    /// render template files into the project, post create hooks are run if `run_hooks` is set
+    pub fn apply<P: AsRef<Path>>(
+        &self,
+        project_path: P,
+        vars: &HashMap<String, String>,
+        run_hooks: bool,
+        signal: &Signal,
+    ) -> Result<()> {
+        let project_path = project_path.as_ref();
+        let context = Context::from_serialize(&self.variables(vars)?)?;
+        for (source_path, target) in self.targets(&context)? {
+            let template = fs::read_to_string(&source_path)
+                .with_context(|| format!("can't read template file {:?}", source_path))?;
+            let content = Tera::one_off(&template, &context, false)
+                .map_err(|err| anyhow!("failed to render {:?}: {}", source_path, err))?;
+            let target_path = project_path.join(&target);
+            if let Some(dir) = target_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&target_path, content)?;
+            println!("Created file {:?}", target);
+        }
+        if !run_hooks {
+            if !self.manifest.post_create.is_empty() {
+                println!("Skip post create hooks");
+            }
+            return Ok(());
+        }
+        for hook in &self.manifest.post_create {
+            println!("Run {}", hook);
+            cli::run(hook.to_string(), project_path, signal)
+                .map_err(|err| anyhow!("post create hook `{}` failed: {}", hook, err))?;
+        }
+        Ok(())
+    }
+}
+
+/// templates cached by `capsule new --template-git`
+pub fn list_cached_templates() -> Result<Vec<ProjectTemplate>> {
+    let cache_dir = templates_cache_dir()?;
+    if !cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut templates = Vec::new();
+    for entry in fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        if path.join(TEMPLATE_MANIFEST).exists() {
+            templates.push(ProjectTemplate::load(path)?);
+        }
+    }
+    templates.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+    Ok(templates)
+}
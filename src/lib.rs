@@ -1,13 +1,23 @@
+pub mod cache;
 pub mod checker;
 pub mod config;
 pub mod config_manipulate;
+pub mod constants;
+pub mod contract_deps;
+pub mod contract_edit;
+pub mod coverage;
 pub mod debugger;
 pub mod deployment;
+pub mod deps;
+pub mod fuzzer;
 pub mod generator;
+pub mod lint;
+pub mod molecule;
 pub mod project_context;
 pub mod recipe;
 pub mod signal;
 pub mod tester;
+pub mod upgrade;
 pub mod util;
 pub mod version;
 pub mod wallet;
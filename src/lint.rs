@@ -0,0 +1,196 @@
+//! Static checks of contracts
+//!
+//! Each recipe lints its contracts: Rust contracts are checked by clippy in the build image,
+//! C contracts are compiled with extra warnings in the builder image and checked by
+//! `clang-tidy` if it is installed. Sources are also scanned for `std` usage, floating point,
+//! heavy dependencies and debug output which is kept in release builds.
+//! Findings of all contracts are aggregated into one report.
+
+use crate::config::Contract;
+use crate::project_context::Context;
+use crate::recipe::get_recipe;
+use crate::signal::Signal;
+use anyhow::Result;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub contract: String,
+    pub severity: Severity,
+    /// name of the check, e.g. `clippy` or `no-std`
+    pub check: &'static str,
+    /// `path:line` of the finding, relative to the project
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(
+        contract: &Contract,
+        severity: Severity,
+        check: &'static str,
+        location: Option<String>,
+        message: String,
+    ) -> Self {
+        Finding {
+            contract: contract.name.clone(),
+            severity,
+            check,
+            location,
+            message,
+        }
+    }
+}
+
+/// a pattern which is reported if it's found in source lines
+pub struct SourcePattern {
+    pub check: &'static str,
+    pub severity: Severity,
+    pub pattern: &'static str,
+    /// match whole identifiers instead of substrings
+    pub token: bool,
+    pub message: &'static str,
+}
+
+impl SourcePattern {
+    fn matches(&self, line: &str) -> bool {
+        if self.token {
+            line.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .any(|token| token == self.pattern)
+        } else {
+            line.contains(self.pattern)
+        }
+    }
+}
+
+/// source files with the extensions under the dir
+pub fn source_files(dir: &Path, exts: &[&str]) -> Result<Vec<PathBuf>> {
+    fn collect(dir: &Path, exts: &[&str], files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(&path, exts, files)?;
+            } else if path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| exts.contains(&ext))
+                .unwrap_or(false)
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        collect(dir, exts, &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// display path relative to the project
+pub fn location(context: &Context, path: &Path, line: Option<usize>) -> String {
+    let path = path.strip_prefix(&context.project_path).unwrap_or(path);
+    match line {
+        Some(line) => format!("{}:{}", path.display(), line),
+        None => path.display().to_string(),
+    }
+}
+
+/// scan source lines for the patterns, comment lines are skipped
+pub fn scan_sources(
+    context: &Context,
+    contract: &Contract,
+    files: &[PathBuf],
+    patterns: &[SourcePattern],
+) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    for path in files {
+        let content = fs::read_to_string(path)?;
+        for (i, line) in content.lines().enumerate() {
+            let code = line.trim_start();
+            if code.starts_with("//") || code.starts_with("/*") || code.starts_with('*') {
+                continue;
+            }
+            for pattern in patterns.iter().filter(|p| p.matches(code)) {
+                findings.push(Finding::new(
+                    contract,
+                    pattern.severity,
+                    pattern.check,
+                    Some(location(context, path, Some(i + 1))),
+                    pattern.message.to_string(),
+                ));
+            }
+        }
+    }
+    Ok(findings)
+}
+
+pub struct Lint<'a> {
+    context: &'a Context,
+}
+
+impl<'a> Lint<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        Lint { context }
+    }
+
+    /// lint contracts, tool failures are reported as findings
+    pub fn run(&self, contracts: &[Contract], signal: &Signal) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        for contract in contracts {
+            println!("Linting contract {}", contract.name);
+            let recipe = get_recipe(self.context.clone(), contract.template_type)?;
+            findings.extend(recipe.lint(contract, signal)?);
+        }
+        Ok(findings)
+    }
+}
+
+/// print findings grouped by contract, returns the number of errors
+pub fn print_report(findings: &[Finding]) -> usize {
+    let mut findings: Vec<_> = findings.iter().collect();
+    findings.sort_by(|a, b| {
+        (&a.contract, b.severity, &a.location).cmp(&(&b.contract, a.severity, &b.location))
+    });
+    println!("------------------------------");
+    for finding in &findings {
+        match finding.location.as_ref() {
+            Some(location) => println!(
+                "{}\t{}[{}]\t{}: {}",
+                finding.contract, finding.severity, finding.check, location, finding.message
+            ),
+            None => println!(
+                "{}\t{}[{}]\t{}",
+                finding.contract, finding.severity, finding.check, finding.message
+            ),
+        }
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if !findings.is_empty() {
+        println!("------------------------------");
+    }
+    println!("{} errors, {} warnings", errors, findings.len() - errors);
+    errors
+}
@@ -0,0 +1,310 @@
+//! Molecule schema code generation
+//!
+//! Schemas listed in `capsule.toml` are compiled by `moleculec` in the build image:
+//!
+//! ```toml
+//! [molecule]
+//! schemas = ["schemas/my_types.mol"]
+//! ```
+//!
+//! Outputs are cached under `build/molecule`, then copied into
+//! `src/molecule` of Rust contracts and tests, and `common/molecule` of C contracts.
+
+use crate::config::TemplateType;
+use crate::config_manipulate::{append_cargo_dependency, append_cargo_no_std_dependency, Document};
+use crate::project_context::{read_config_file, write_config_file, Context, CARGO_CONFIG_FILE};
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::util::docker::DockerCommand;
+use crate::util::fs::{latest_modified, write_if_changed};
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MOLECULE_BUILD_DIR: &str = "build/molecule";
+const MOLECULE_DIR: &str = "molecule";
+pub const MOLECULEC_BIN: &str = "moleculec";
+const MOLECULE_CRATE: &str = "molecule";
+/// version of the molecule crate, moleculec must be the same minor version
+pub const MOLECULE_VERSION: &str = "0.6";
+const GENERATED_HEADER: &str = "// Generated by capsule from molecule schemas, don't edit";
+const SCHEMA_EXT: &str = "mol";
+
+#[derive(Clone, Copy)]
+enum Language {
+    Rust,
+    C,
+}
+
+impl Language {
+    fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::C => "c",
+        }
+    }
+
+    fn ext(self) -> &'static str {
+        match self {
+            Language::Rust => "rs",
+            Language::C => "h",
+        }
+    }
+}
+
+pub struct Molecule<'a> {
+    context: &'a Context,
+}
+
+impl<'a> Molecule<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        Molecule { context }
+    }
+
+    fn schema_path(&self, schema: &Path) -> PathBuf {
+        let mut path = self.context.project_path.clone();
+        path.push(schema);
+        path
+    }
+
+    fn schema_stem(schema: &Path) -> Result<&str> {
+        schema
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or(anyhow!("invalid schema path {:?}", schema))
+    }
+
+    fn output_path(&self, schema: &Path, language: Language) -> Result<PathBuf> {
+        let mut path = self.context.project_path.clone();
+        path.push(MOLECULE_BUILD_DIR);
+        path.push(language.name());
+        path.push(format!("{}.{}", Self::schema_stem(schema)?, language.ext()));
+        Ok(path)
+    }
+
+    /// the schema and schemas imported by it recursively
+    fn schema_files(schema_path: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = vec![fs::canonicalize(schema_path)?];
+        let mut i = 0;
+        while i < files.len() {
+            let content = fs::read_to_string(&files[i])?;
+            let dir = files[i].parent().expect("dir").to_path_buf();
+            for import in parse_imports(&content) {
+                let path = dir.join(format!("{}.{}", import, SCHEMA_EXT));
+                if !path.exists() {
+                    return Err(anyhow!(
+                        "schema {:?} imports {:?} which doesn't exist",
+                        files[i],
+                        path
+                    ));
+                }
+                let path = fs::canonicalize(path)?;
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+            i += 1;
+        }
+        Ok(files)
+    }
+
+    fn rel_path(&self, path: &Path) -> Result<String> {
+        Ok(path
+            .strip_prefix(&self.context.project_path)
+            .map_err(|_| anyhow!("{:?} must be under the project dir", path))?
+            .to_str()
+            .expect("path")
+            .to_string())
+    }
+
+    fn run_moleculec(&self, cmd: String, signal: &Signal) -> Result<()> {
+        let project_path = self.context.project_path.to_str().expect("path");
+        DockerCommand::with_context(
+            self.context,
+            DOCKER_IMAGE.to_string(),
+            project_path.to_string(),
+        )
+        .fix_dir_permission(MOLECULE_BUILD_DIR.to_string())
+        .run(cmd, signal)
+        .map_err(|err| {
+            anyhow!(
+                "{}\nhint: make sure `{}` is installed in the build image",
+                err,
+                MOLECULEC_BIN
+            )
+        })
+    }
+
+    /// Validate schemas, returns errors of invalid schemas
+    pub fn check(&self, signal: &Signal) -> Result<Vec<(PathBuf, String)>> {
+        let mut errors = Vec::new();
+        let mut stems = HashSet::new();
+        for schema in &self.context.config.molecule.schemas {
+            let path = self.schema_path(schema);
+            if !path.exists() {
+                errors.push((schema.clone(), "schema file not exists".to_string()));
+                continue;
+            }
+            let stem = Self::schema_stem(schema)?;
+            if !stems.insert(stem.to_string()) {
+                errors.push((schema.clone(), format!("duplicated schema name '{}'", stem)));
+                continue;
+            }
+            // the intermediate format is generated to validate the schema
+            let cmd = format!(
+                "{} --language - --format json --schema-file {} > /dev/null",
+                MOLECULEC_BIN,
+                self.rel_path(&path)?
+            );
+            if let Err(err) = self.run_moleculec(cmd, signal) {
+                errors.push((schema.clone(), err.to_string()));
+            }
+        }
+        Ok(errors)
+    }
+
+    /// generate code of outdated schemas, returns number of generated files
+    fn compile(&self, language: Language, signal: &Signal) -> Result<usize> {
+        let mut count = 0;
+        for schema in &self.context.config.molecule.schemas {
+            let schema_path = self.schema_path(schema);
+            let output_path = self.output_path(schema, language)?;
+            if !schema_path.exists() {
+                return Err(anyhow!("schema file not exists {:?}", schema_path));
+            }
+            // outputs are outdated if the schema or any imported schema is modified
+            let mut schema_modified = None;
+            for path in Self::schema_files(&schema_path)? {
+                schema_modified = schema_modified.max(latest_modified(path)?);
+            }
+            if latest_modified(&output_path)? >= schema_modified {
+                continue;
+            }
+            fs::create_dir_all(output_path.parent().expect("dir"))?;
+            println!("Generate {} code of {:?}", language.name(), schema);
+            let cmd = format!(
+                "{} --language {} --schema-file {} > {}",
+                MOLECULEC_BIN,
+                language.name(),
+                self.rel_path(&schema_path)?,
+                self.rel_path(&output_path)?
+            );
+            self.run_moleculec(cmd, signal)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// copy generated files into dir, unchanged files are not touched to avoid rebuilds
+    fn install(&self, language: Language, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let mut modules = Vec::new();
+        for schema in &self.context.config.molecule.schemas {
+            let content = fs::read(self.output_path(schema, language)?)?;
+            let stem = Self::schema_stem(schema)?;
+            write_if_changed(&dir.join(format!("{}.{}", stem, language.ext())), &content)?;
+            modules.push(stem.to_string());
+        }
+        if let Language::Rust = language {
+            let mut mod_rs = format!(
+                "{}\n#![allow(dead_code)]\n#![allow(clippy::all)]\n\n",
+                GENERATED_HEADER
+            );
+            for module in modules {
+                mod_rs.push_str(&format!("pub mod {};\n", module));
+            }
+            write_if_changed(&dir.join("mod.rs"), mod_rs.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn add_rust_dependency(&self, crate_dir: &Path, no_std: bool) -> Result<()> {
+        let cargo_path = crate_dir.join(CARGO_CONFIG_FILE);
+        if !cargo_path.exists() {
+            return Ok(());
+        }
+        let mut doc = read_config_file(&cargo_path)?.parse::<Document>()?;
+        let changed = if no_std {
+            append_cargo_no_std_dependency(&mut doc, MOLECULE_CRATE, MOLECULE_VERSION)?
+        } else {
+            append_cargo_dependency(&mut doc, MOLECULE_CRATE, MOLECULE_VERSION)?
+        };
+        if changed {
+            println!("Rewrite {:?}", cargo_path);
+            write_config_file(&cargo_path, doc.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Generate code for contracts and tests
+    pub fn generate(&self, signal: &Signal) -> Result<()> {
+        if self.context.config.molecule.schemas.is_empty() {
+            return Ok(());
+        }
+        let contracts = &self.context.config.contracts;
+        let rust_contracts: Vec<_> = contracts
+            .iter()
+            .filter(|c| match c.template_type {
+                TemplateType::Rust | TemplateType::RustSharedLib => true,
+                _ => false,
+            })
+            .collect();
+        let has_c_contracts = contracts.len() > rust_contracts.len();
+
+        self.compile(Language::Rust, signal)?;
+        for contract in rust_contracts {
+            let mut crate_dir = self.context.contracts_path();
+            crate_dir.push(&contract.name);
+            self.install(Language::Rust, &crate_dir.join("src").join(MOLECULE_DIR))?;
+            self.add_rust_dependency(&crate_dir, true)?;
+        }
+        let tests_dir = self.context.tests_path();
+        if tests_dir.exists() {
+            self.install(Language::Rust, &tests_dir.join("src").join(MOLECULE_DIR))?;
+            self.add_rust_dependency(&tests_dir, false)?;
+        }
+
+        if has_c_contracts {
+            self.compile(Language::C, signal)?;
+            let mut dir = self.context.contracts_path();
+            dir.push("c");
+            dir.push("common");
+            dir.push(MOLECULE_DIR);
+            self.install(Language::C, &dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// paths of `import` statements of a schema, without the extension
+fn parse_imports(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            let rest = line.strip_prefix("import")?;
+            if !rest.starts_with(char::is_whitespace) {
+                return None;
+            }
+            rest.trim().strip_suffix(';').map(str::trim)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_imports() {
+        let content = "\
+import blockchain;
+import  common/types ; // shared types
+// import commented;
+importer;
+array Byte32 [byte; 32];
+";
+        assert_eq!(parse_imports(content), vec!["blockchain", "common/types"]);
+    }
+}
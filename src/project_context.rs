@@ -1,6 +1,6 @@
 /// Project Context
 use crate::config::{Config, Deployment};
-use crate::version::version_string;
+use crate::version::{is_compatible, version_string};
 use anyhow::{anyhow, Result};
 use log::error;
 use std::env;
@@ -9,6 +9,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 pub const CONTRACTS_DIR: &str = "contracts";
+pub const TESTS_DIR: &str = "tests";
 const CONTRACTS_BUILD_DIR: &str = "build";
 const MIGRATIONS_DIR: &str = "migrations";
 pub const CONFIG_FILE: &str = "capsule.toml";
@@ -76,16 +77,15 @@ impl Context {
             read_config_file(config_path)?
         };
         let config: Config = toml::from_slice(content.as_bytes()).expect("parse config");
-        let capsule_version = version_string();
-        if config.version != capsule_version {
+        if !is_compatible(&config.version) {
             let project_version = if config.version.is_empty() {
                 "(Unknown)".to_string()
             } else {
                 config.version.to_string()
             };
             return Err(anyhow!(
-                "Please use the right capsule version, Capsule version: {}, Project version: {}",
-                capsule_version,
+                "Please use the right capsule version, Capsule version: {}, Project version: {}\nhint: run `capsule upgrade-project` to upgrade the project",
+                version_string(),
                 project_version
             ));
         }
@@ -101,6 +101,12 @@ impl Context {
         path
     }
 
+    pub fn tests_path(&self) -> PathBuf {
+        let mut path = self.project_path.clone();
+        path.push(TESTS_DIR);
+        path
+    }
+
     pub fn contracts_build_path(&self, env: BuildEnv) -> PathBuf {
         let mut path = self.project_path.clone();
         path.push(CONTRACTS_BUILD_DIR);
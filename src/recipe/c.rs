@@ -1,15 +1,19 @@
 use crate::config::Contract;
+use crate::deps::{ckb_c_stdlib, CDeps};
 use crate::generator::{CreateContract, TEMPLATES};
-use crate::project_context::{BuildConfig, BuildEnv, Context, CONTRACTS_DIR};
+use crate::lint::{scan_sources, source_files, Finding, Severity, SourcePattern};
+use crate::project_context::{BuildConfig, BuildEnv, Context};
 use crate::recipe::Recipe;
 use crate::signal::Signal;
+use crate::util::changes::Changes;
 use crate::util::cli;
-use crate::util::git;
+use crate::util::fs::{copy_dir, relative_symlink};
 use anyhow::{anyhow, Result};
 use std::fs;
 use std::io::Write;
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use tera;
 
 // Files
@@ -21,19 +25,61 @@ const MAKEFILE: &str = "Makefile";
 const C_DIR_PREFIX: &str = "c";
 const DEPS_DIR_PREFIX: &str = "deps";
 const SRC_DIR_PREFIX: &str = "src";
+const COMMON_DIR_PREFIX: &str = "common";
+const OBJ_DIR: &str = "obj";
+const FLAGS_FILE: &str = "flags";
+const CODE_HASHES_HEADER: &str = "code_hashes.h";
+const UNSTRIPPED_EXT: &str = "unstripped";
 const DEBUG_DIR: &str = "build/debug";
 const RELEASE_DIR: &str = "build/release";
 
-// Deps
-
-const CKB_C_STDLIB_URL: &str = "https://github.com/nervosnetwork/ckb-c-stdlib.git";
-const CKB_C_STDLIB_COMMIT: &str = "82bc1ab07572ceacd1e016488f0a1ac7725ad3c6";
-const CKB_C_STDLIB_NAME: &str = "ckb-c-stdlib";
+// Lint
+
+/// extra warnings of the lint build, `-Wall -Werror` are in the Makefile
+const LINT_CFLAGS: &str = "-Wextra -Wno-unused-parameter";
+const CLANG_TIDY_BIN: &str = "clang-tidy";
+const CLANG_TIDY_CHECKS: &str = "-*,clang-analyzer-*,bugprone-*";
+/// include dirs of the Makefile, relative to the C dir
+const CLANG_TIDY_FLAGS: &str = "-nostdinc -I deps/ckb-c-stdlib -I deps/ckb-c-stdlib/libc -I deps -I deps/ckb-c-stdlib/molecule -I c -I src -I common -I build";
+const SOURCE_PATTERNS: &[SourcePattern] = &[
+    SourcePattern {
+        check: "float",
+        severity: Severity::Warning,
+        pattern: "float",
+        token: true,
+        message: "floating point is emulated by software on CKB-VM and costs many cycles",
+    },
+    SourcePattern {
+        check: "float",
+        severity: Severity::Warning,
+        pattern: "double",
+        token: true,
+        message: "floating point is emulated by software on CKB-VM and costs many cycles",
+    },
+    SourcePattern {
+        check: "release-debug",
+        severity: Severity::Warning,
+        pattern: "ckb_debug(",
+        token: false,
+        message: "the debug syscall is kept in release builds, use `printf` which is only enabled in debug builds",
+    },
+];
 
 pub trait CRecipe {
     fn bin_name(name: &str) -> String;
+    fn src_ext() -> &'static str {
+        "c"
+    }
     fn src_template() -> &'static str;
     fn build_template() -> &'static str;
+    /// files generated into the common dir along with the first contract
+    fn support_templates() -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+    /// variables required by the build template, appended to Makefile if missing
+    fn makefile_vars_template() -> Option<&'static str> {
+        None
+    }
 }
 pub struct CBin;
 
@@ -66,6 +112,45 @@ impl CRecipe for CSharedLib {
     }
 }
 
+pub struct CppBin;
+
+impl CRecipe for CppBin {
+    fn bin_name(name: &str) -> String {
+        name.to_string()
+    }
+
+    fn src_ext() -> &'static str {
+        "cpp"
+    }
+
+    fn src_template() -> &'static str {
+        "cpp/contract/example.cpp"
+    }
+
+    fn build_template() -> &'static str {
+        "cpp/contract/BUILD"
+    }
+
+    fn support_templates() -> &'static [(&'static str, &'static str)] {
+        &[
+            ("ckb_cpp_runtime.h", "cpp/ckb_cpp_runtime.h"),
+            ("ckb_cpp_runtime.cpp", "cpp/ckb_cpp_runtime.cpp"),
+        ]
+    }
+
+    fn makefile_vars_template() -> Option<&'static str> {
+        Some("cpp/Makefile-vars")
+    }
+}
+
+/// escape a value of a makefile variable, the value is passed to the shell as it is
+fn make_escape(value: &str) -> String {
+    value
+        .replace('$', "$$")
+        .replace('#', "\\#")
+        .replace('\n', " ")
+}
+
 pub struct C<R> {
     context: Context,
     phantom_data: PhantomData<R>,
@@ -91,13 +176,33 @@ impl<R: CRecipe> C<R> {
         src_path
     }
 
+    fn common_dir(&self) -> PathBuf {
+        let mut path = self.c_dir();
+        path.push(COMMON_DIR_PREFIX);
+        path
+    }
+
+    /// sources and headers of the contract
+    fn contract_src_dir(&self, name: &str) -> PathBuf {
+        let mut path = self.src_dir();
+        path.push(name);
+        path
+    }
+
+    /// contracts created before per-contract dirs are single files
+    fn legacy_source_path(&self, name: &str) -> PathBuf {
+        let mut path = self.src_dir();
+        path.push(format!("{}.{}", name, R::src_ext()));
+        path
+    }
+
     fn makefile_path(&self) -> PathBuf {
         let mut p = self.c_dir();
         p.push(MAKEFILE);
         p
     }
 
-    fn setup_c_environment(&self) -> Result<()> {
+    fn setup_c_environment(&self, signal: &Signal) -> Result<()> {
         println!("Setup C environment");
         let c_dir = self.c_dir();
         if c_dir.exists() {
@@ -107,26 +212,22 @@ impl<R: CRecipe> C<R> {
         // Setup Dirs
         fs::create_dir(&c_dir)?;
 
-        for prefix in &[DEPS_DIR_PREFIX, SRC_DIR_PREFIX] {
+        for prefix in &[DEPS_DIR_PREFIX, SRC_DIR_PREFIX, COMMON_DIR_PREFIX] {
             let mut dir = c_dir.clone();
             dir.push(prefix);
             fs::create_dir(&dir)?;
         }
 
-        // Pull deps
-        let rel_path = format!(
-            "{contracts}/{c}/{deps}/{name}",
-            contracts = CONTRACTS_DIR,
-            c = C_DIR_PREFIX,
-            deps = DEPS_DIR_PREFIX,
-            name = CKB_C_STDLIB_NAME
-        );
-        git::add_submodule(
-            &self.context,
-            CKB_C_STDLIB_URL,
-            rel_path.as_str(),
-            CKB_C_STDLIB_COMMIT,
-        )?;
+        // Declare and fetch deps, the contract can still be created without network
+        let deps = CDeps::new(&self.context);
+        let dep = ckb_c_stdlib();
+        deps.declare(&dep)?;
+        if let Err(err) = deps.fetch_deps(&[dep], signal) {
+            println!(
+                "Failed to fetch C dependencies: {}\nhint: run `capsule deps fetch` later",
+                err
+            );
+        }
 
         // Generate files
         for f in &["Makefile"] {
@@ -140,8 +241,116 @@ impl<R: CRecipe> C<R> {
         Ok(())
     }
 
-    fn source_name(&self, name: &str) -> String {
-        format!("{}.c", name)
+    fn source_name(&self) -> String {
+        format!("main.{}", R::src_ext())
+    }
+
+    /// write flags of the contract as a makefile which is passed to make with `-f`,
+    /// changes of the flags trigger a rebuild, the file is a prerequisite of objects.
+    /// returns the path relative to the C dir
+    fn write_flags(&self, build_env: BuildEnv, c: &Contract) -> Result<String> {
+        let env_dir = match build_env {
+            BuildEnv::Debug => DEBUG_DIR,
+            BuildEnv::Release => RELEASE_DIR,
+        };
+        let rel_path = format!("{}/{}/{}/{}", env_dir, OBJ_DIR, c.name, FLAGS_FILE);
+        let mut path = self.c_dir();
+        path.push(&rel_path);
+        fs::create_dir_all(path.parent().expect("flags dir"))?;
+        let content = format!(
+            "# flags of contract {}, generated by capsule\nCONTRACT_CFLAGS := {}\nCONTRACT_LDFLAGS := {}\n",
+            c.name,
+            make_escape(c.cflags.as_deref().unwrap_or_default()),
+            make_escape(c.ldflags.as_deref().unwrap_or_default())
+        );
+        if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+            fs::write(&path, content)?;
+        }
+        Ok(rel_path)
+    }
+
+    /// append variables of the recipe and rules of the contract to the Makefile
+    fn append_build_rules(&self, name: &str) -> Result<()> {
+        println!("Rewrite Makefile");
+        let context = tera::Context::from_serialize(&CreateContract {
+            name: name.to_string(),
+        })?;
+        if let Some(vars_template) = R::makefile_vars_template() {
+            let vars = TEMPLATES.render(&format!("c/{}", vars_template), &context)?;
+            let makefile = fs::read_to_string(self.makefile_path())?;
+            if !makefile.contains(vars.trim()) {
+                fs::OpenOptions::new()
+                    .append(true)
+                    .open(self.makefile_path())?
+                    .write_all(vars.as_bytes())?;
+            }
+        }
+        let content = self.build_rules(name)?;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(self.makefile_path())?
+            .write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// rules of the contract in the Makefile
+    fn build_rules(&self, name: &str) -> Result<String> {
+        let context = tera::Context::from_serialize(&CreateContract {
+            name: name.to_string(),
+        })?;
+        Ok(TEMPLATES.render(&format!("c/{}", R::build_template()), &context)?)
+    }
+
+    /// replace rules of the contract in the Makefile, rules edited by hand are left to the user
+    fn replace_build_rules(
+        &self,
+        name: &str,
+        new_rules: &str,
+        changes: &mut Changes,
+    ) -> Result<()> {
+        let rules = self.build_rules(name)?;
+        let makefile = changes.read(self.makefile_path())?.unwrap_or_default();
+        if makefile.contains(&rules) {
+            changes.write(
+                self.makefile_path(),
+                makefile.replacen(&rules, new_rules, 1),
+            )?;
+        } else {
+            changes.note(format!(
+                "can't find generated rules of {} in {:?}, please edit it by hand",
+                name,
+                self.makefile_path()
+            ));
+        }
+        Ok(())
+    }
+
+    /// intermediate outputs under the C dir, they are rebuilt on demand
+    fn remove_build_outputs(&self, name: &str, changes: &mut Changes) {
+        for build_env in &[BuildEnv::Debug, BuildEnv::Release] {
+            let target = self.build_target(*build_env, name);
+            let mut path = self.c_dir();
+            path.push(&target);
+            changes.remove_path(&path);
+            changes.remove_path(path.with_file_name(format!("{}.debug", R::bin_name(name))));
+            changes.remove_path(path.with_file_name(format!(
+                "{}.{}",
+                R::bin_name(name),
+                UNSTRIPPED_EXT
+            )));
+            let mut obj_dir = path.with_file_name(OBJ_DIR);
+            obj_dir.push(name);
+            changes.remove_path(obj_dir);
+        }
+    }
+
+    /// make the target in the builder image, flags of the contract are read from the flags file,
+    /// so they are never quoted in the command line
+    fn make_cmd(&self, build_target: &str, flags_path: &str) -> String {
+        format!(
+            "make via-docker ARGS=\"-f {} -f {} {}\"",
+            MAKEFILE, flags_path, build_target
+        )
     }
 
     fn build_target(&self, build_env: BuildEnv, name: &str) -> String {
@@ -154,19 +363,17 @@ impl<R: CRecipe> C<R> {
 
 impl<R: CRecipe> Recipe for C<R> {
     fn exists(&self, name: &str) -> bool {
-        let mut src = self.src_dir();
-        src.push(self.source_name(name));
-        src.exists()
+        self.contract_src_dir(name).exists() || self.legacy_source_path(name).exists()
     }
 
     fn create_contract(
         &self,
         contract: &Contract,
         rewrite_config: bool,
-        _signal: &Signal,
+        signal: &Signal,
     ) -> Result<()> {
         // setup c environment if needed
-        self.setup_c_environment()?;
+        self.setup_c_environment(signal)?;
 
         // new contract
         let name = &contract.name;
@@ -177,24 +384,64 @@ impl<R: CRecipe> Recipe for C<R> {
         let f = R::src_template();
         let template_path = format!("c/{}", f);
         let content = TEMPLATES.render(&template_path, &context)?;
-        let mut src_path = self.src_dir();
-        src_path.push(self.source_name(name));
+        let mut src_path = self.contract_src_dir(name);
+        fs::create_dir_all(&src_path)?;
+        src_path.push(self.source_name());
         fs::write(src_path, content)?;
+        fs::create_dir_all(self.common_dir())?;
+        for (f, template_name) in R::support_templates() {
+            let mut path = self.common_dir();
+            path.push(f);
+            if !path.exists() {
+                let content = TEMPLATES.render(&format!("c/{}", template_name), &context)?;
+                fs::write(path, content)?;
+            }
+        }
 
         if rewrite_config {
-            println!("Rewrite Makefile");
-            let f = R::build_template();
-            let template_path = format!("c/{}", f);
-            let content = TEMPLATES.render(&template_path, &context)?;
-            let makefile_path = self.makefile_path();
-            fs::OpenOptions::new()
-                .append(true)
-                .open(makefile_path)?
-                .write_all(content.as_bytes())?;
+            self.append_build_rules(name)?;
         }
         Ok(())
     }
 
+    fn import_contract(
+        &self,
+        contract: &Contract,
+        source: &Path,
+        link: bool,
+        signal: &Signal,
+    ) -> Result<()> {
+        self.setup_c_environment(signal)?;
+        let name = &contract.name;
+        let src_dir = self.contract_src_dir(name);
+        println!("Import contract {:?} from {:?}", name, source);
+        // only the C dir is mounted into the build image
+        if source.is_dir() {
+            if link {
+                relative_symlink(source, &src_dir, self.c_dir())?;
+            } else {
+                copy_dir(source, &src_dir)?;
+            }
+        } else {
+            if source.extension().and_then(|ext| ext.to_str()) != Some(R::src_ext()) {
+                return Err(anyhow!(
+                    "expect a .{} source file or a dir, got {:?}",
+                    R::src_ext(),
+                    source
+                ));
+            }
+            fs::create_dir_all(&src_dir)?;
+            let mut src_path = src_dir.clone();
+            src_path.push(self.source_name());
+            if link {
+                relative_symlink(source, &src_path, self.c_dir())?;
+            } else {
+                fs::copy(source, &src_path)?;
+            }
+        }
+        self.append_build_rules(name)
+    }
+
     /// run command
     /// Delegate to cli command
     fn run(&self, _contract: &Contract, build_cmd: String, signal: &Signal) -> Result<()> {
@@ -209,11 +456,8 @@ impl<R: CRecipe> Recipe for C<R> {
         bin_path.push(&build_target);
         // make sure the bin dir is exist
         fs::create_dir_all(&bin_path.parent().ok_or(anyhow!("expect build dir"))?)?;
-        self.run(
-            c,
-            format!("make via-docker ARGS=\"{}\"", &build_target),
-            signal,
-        )?;
+        let flags_path = self.write_flags(config.build_env, c)?;
+        self.run(c, self.make_cmd(&build_target, &flags_path), signal)?;
 
         // copy to build dir
         if !bin_path.exists() {
@@ -234,4 +478,179 @@ impl<R: CRecipe> Recipe for C<R> {
     fn clean(&self, _contracts: &[Contract], signal: &Signal) -> Result<()> {
         cli::run("make clean".to_string(), self.c_dir(), signal)
     }
+
+    fn source_paths(&self, contract: &Contract) -> Vec<PathBuf> {
+        // shared sources and build rules of all contracts
+        let mut paths = vec![self.common_dir(), self.makefile_path()];
+        let legacy_path = self.legacy_source_path(&contract.name);
+        if !legacy_path.exists() {
+            paths.push(self.contract_src_dir(&contract.name));
+            return paths;
+        }
+        paths.push(legacy_path);
+        // headers of single file contracts are beside the sources
+        let headers = fs::read_dir(self.src_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "h"));
+        paths.extend(headers);
+        paths
+    }
+
+    fn binary_path(&self, contract: &Contract, build_env: BuildEnv) -> PathBuf {
+        let mut path = self.context.project_path.clone();
+        path.push(self.build_target(build_env, &contract.name));
+        path
+    }
+
+    fn debug_symbol_path(&self, contract: &Contract, build_env: BuildEnv) -> PathBuf {
+        let mut path = self.c_dir();
+        path.push(format!(
+            "{}.debug",
+            self.build_target(build_env, &contract.name)
+        ));
+        path
+    }
+
+    /// copied by the Makefile rule before stripping
+    fn unstripped_binary_path(&self, contract: &Contract, build_env: BuildEnv) -> Option<PathBuf> {
+        let mut path = self.c_dir();
+        path.push(format!(
+            "{}.{}",
+            self.build_target(build_env, &contract.name),
+            UNSTRIPPED_EXT
+        ));
+        Some(path)
+    }
+
+    fn code_dir(&self) -> PathBuf {
+        self.c_dir()
+    }
+
+    fn code_hashes_path(&self, contract: &Contract) -> PathBuf {
+        if self.legacy_source_path(&contract.name).exists() {
+            let mut path = self.src_dir();
+            path.push(format!("{}_{}", contract.name, CODE_HASHES_HEADER));
+            return path;
+        }
+        let mut path = self.contract_src_dir(&contract.name);
+        path.push(CODE_HASHES_HEADER);
+        path
+    }
+
+    fn rename_contract(
+        &self,
+        contract: &Contract,
+        new_name: &str,
+        changes: &mut Changes,
+    ) -> Result<()> {
+        let new_rules = self.build_rules(new_name)?;
+        self.replace_build_rules(&contract.name, &new_rules, changes)?;
+        if self.legacy_source_path(&contract.name).exists() {
+            changes.move_path(
+                self.legacy_source_path(&contract.name),
+                self.legacy_source_path(new_name),
+            );
+        } else {
+            changes.move_path(
+                self.contract_src_dir(&contract.name),
+                self.contract_src_dir(new_name),
+            );
+        }
+        self.remove_build_outputs(&contract.name, changes);
+        Ok(())
+    }
+
+    fn remove_contract(&self, contract: &Contract, changes: &mut Changes) -> Result<()> {
+        self.replace_build_rules(&contract.name, "", changes)?;
+        changes.remove_path(self.legacy_source_path(&contract.name));
+        changes.remove_path(self.contract_src_dir(&contract.name));
+        self.remove_build_outputs(&contract.name, changes);
+        Ok(())
+    }
+
+    fn lint(&self, c: &Contract, signal: &Signal) -> Result<Vec<Finding>> {
+        let legacy_path = self.legacy_source_path(&c.name);
+        let files = if legacy_path.exists() {
+            vec![legacy_path]
+        } else {
+            source_files(&self.contract_src_dir(&c.name), &[R::src_ext(), "h"])?
+        };
+        let mut findings = scan_sources(&self.context, c, &files, SOURCE_PATTERNS)?;
+
+        // build with extra warnings, objects are rebuilt since the flags are changed
+        let lint_contract = Contract {
+            cflags: Some(
+                format!(
+                    "{} {}",
+                    c.cflags.as_deref().unwrap_or_default(),
+                    LINT_CFLAGS
+                )
+                .trim()
+                .to_string(),
+            ),
+            ..c.clone()
+        };
+        let build_target = self.build_target(BuildEnv::Debug, &c.name);
+        let mut bin_path = self.c_dir();
+        bin_path.push(&build_target);
+        fs::create_dir_all(&bin_path.parent().ok_or(anyhow!("expect build dir"))?)?;
+        let flags_path = self.write_flags(BuildEnv::Debug, &lint_contract)?;
+        if let Err(err) = self.run(c, self.make_cmd(&build_target, &flags_path), signal) {
+            findings.push(Finding::new(
+                c,
+                Severity::Error,
+                "warnings",
+                None,
+                format!(
+                    "compiler reported warnings with `{}`, see the output above ({})",
+                    LINT_CFLAGS, err
+                ),
+            ));
+        }
+
+        // clang-tidy is optional, it runs on the host
+        let clang_tidy_installed = Command::new(CLANG_TIDY_BIN)
+            .arg("--version")
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        if !clang_tidy_installed {
+            println!("{} not found, skip it", CLANG_TIDY_BIN);
+            return Ok(findings);
+        }
+        let c_dir = self.c_dir();
+        let sources: Vec<_> = files
+            .iter()
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some(R::src_ext()))
+            .filter_map(|path| path.strip_prefix(&c_dir).ok())
+            .filter_map(|path| path.to_str())
+            .collect();
+        if sources.is_empty() {
+            return Ok(findings);
+        }
+        let clang_tidy_cmd = format!(
+            "{} --quiet --checks='{}' --warnings-as-errors='*' {} -- {} -I src/{} {}",
+            CLANG_TIDY_BIN,
+            CLANG_TIDY_CHECKS,
+            sources.join(" "),
+            CLANG_TIDY_FLAGS,
+            c.name,
+            c.cflags.as_deref().unwrap_or_default()
+        );
+        if let Err(err) = cli::run(clang_tidy_cmd, &c_dir, signal) {
+            findings.push(Finding::new(
+                c,
+                Severity::Error,
+                "clang-tidy",
+                None,
+                format!(
+                    "clang-tidy reported problems, see the output above ({})",
+                    err
+                ),
+            ));
+        }
+        Ok(findings)
+    }
 }
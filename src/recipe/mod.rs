@@ -2,15 +2,22 @@ mod c;
 pub mod rust;
 
 use crate::config::{Contract, TemplateType};
-use crate::project_context::{BuildConfig, Context};
+use crate::lint::Finding;
+use crate::project_context::{BuildConfig, BuildEnv, Context};
 use crate::signal::Signal;
+use crate::util::changes::Changes;
 use anyhow::Result;
+use std::path::{Path, PathBuf};
 
 pub fn get_recipe(context: Context, template_type: TemplateType) -> Result<Box<dyn Recipe>> {
     match template_type {
-        TemplateType::Rust => Ok(Box::new(rust::Rust::new(context))),
+        TemplateType::Rust => Ok(Box::new(rust::Rust::<rust::RustBin>::new(context))),
+        TemplateType::RustSharedLib => {
+            Ok(Box::new(rust::Rust::<rust::RustSharedLib>::new(context)))
+        }
         TemplateType::C => Ok(Box::new(c::C::<c::CBin>::new(context))),
         TemplateType::CSharedLib => Ok(Box::new(c::C::<c::CSharedLib>::new(context))),
+        TemplateType::Cpp => Ok(Box::new(c::C::<c::CppBin>::new(context))),
     }
 }
 
@@ -22,7 +29,38 @@ pub trait Recipe {
         rewrite_config: bool,
         signal: &Signal,
     ) -> Result<()>;
+    /// copy or link existing sources as the contract
+    fn import_contract(
+        &self,
+        contract: &Contract,
+        source: &Path,
+        link: bool,
+        signal: &Signal,
+    ) -> Result<()>;
     fn run(&self, contract: &Contract, build_cmd: String, signal: &Signal) -> Result<()>;
     fn run_build(&self, contract: &Contract, config: BuildConfig, signal: &Signal) -> Result<()>;
     fn clean(&self, contracts: &[Contract], signal: &Signal) -> Result<()>;
+    /// source files of the contract, used to detect outdated binaries
+    fn source_paths(&self, contract: &Contract) -> Vec<PathBuf>;
+    /// path of the contract binary under the project build dir
+    fn binary_path(&self, contract: &Contract, build_env: BuildEnv) -> PathBuf;
+    /// path of the unstripped binary which contains the debug info
+    fn debug_symbol_path(&self, contract: &Contract, build_env: BuildEnv) -> PathBuf;
+    /// runnable copy of the binary which keeps the symbols, None if the build strips the binary
+    fn unstripped_binary_path(&self, contract: &Contract, build_env: BuildEnv) -> Option<PathBuf>;
+    /// host dir which is mounted as `/code` in the build image, debug info refers to sources under it
+    fn code_dir(&self) -> PathBuf;
+    /// generated code hashes of dependencies, `.rs` or `.h` file in the contract sources
+    fn code_hashes_path(&self, contract: &Contract) -> PathBuf;
+    /// rename sources and build configs of the contract
+    fn rename_contract(
+        &self,
+        contract: &Contract,
+        new_name: &str,
+        changes: &mut Changes,
+    ) -> Result<()>;
+    /// remove sources and build configs of the contract
+    fn remove_contract(&self, contract: &Contract, changes: &mut Changes) -> Result<()>;
+    /// static checks of the contract, failures of lint tools are reported as findings
+    fn lint(&self, contract: &Contract, signal: &Signal) -> Result<Vec<Finding>>;
 }
@@ -1,18 +1,25 @@
 use crate::config::Contract;
-use crate::config_manipulate::{append_cargo_workspace_member, Document};
+use crate::config_manipulate::{
+    append_cargo_workspace_member, remove_cargo_workspace_member, rename_cargo_workspace_member,
+    value, Document,
+};
 use crate::generator::{CreateContract, TEMPLATES};
+use crate::lint::{location, scan_sources, source_files, Finding, Severity, SourcePattern};
 use crate::project_context::{
     read_config_file, write_config_file, BuildConfig, BuildEnv, Context, CARGO_CONFIG_FILE,
     CONTRACTS_DIR,
 };
 use crate::recipe::Recipe;
 use crate::signal::Signal;
+use crate::util::changes::Changes;
 use crate::util::docker::DockerCommand;
+use crate::util::fs::{copy_dir, relative_symlink};
 use anyhow::{anyhow, Result};
 use tera;
 
 use std::fs;
-use std::path::PathBuf;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 
 pub const DOCKER_IMAGE: &str = "jjy0/ckb-capsule-recipe-rust:2020-9-28";
 const RUST_TARGET: &str = "riscv64imac-unknown-none-elf";
@@ -21,14 +28,159 @@ const BASE_RUSTFLAGS: &str =
     "-Z pre-link-arg=-zseparate-code -Z pre-link-arg=-zseparate-loadable-segments";
 const RELEASE_RUSTFLAGS: &str = "-C link-arg=-s";
 const ALWAYS_DEBUG_RUSTFLAGS: &str = "--cfg=debug_assertions";
+const SHARED_LIB_RUSTFLAGS: &str = "-C relocation-model=pic";
+const GCC_BIN: &str = "riscv64-unknown-elf-gcc";
+/// linker flags of `BASE_RUSTFLAGS` and `RELEASE_RUSTFLAGS` for the shared library link
+const SHARED_LIB_LINK_FLAGS: &str = "-Wl,-zseparate-code -Wl,-zseparate-loadable-segments";
+const SHARED_LIB_RELEASE_LINK_FLAGS: &str = "-s";
+const CODE_HASHES_FILE: &str = "code_hashes.rs";
+/// lints of clippy for scripts, floating point is emulated and panics abort the script
+const CLIPPY_LINTS: &str = "-W clippy::float_arithmetic -W clippy::unwrap_used -W clippy::mem_forget -W clippy::large_stack_arrays";
+/// dependencies which are too heavy for scripts or require `std`
+const HEAVY_DEPENDENCIES: &[&str] = &[
+    "serde_json",
+    "regex",
+    "chrono",
+    "rand",
+    "tokio",
+    "reqwest",
+    "openssl",
+    "num-bigint",
+];
+const SOURCE_PATTERNS: &[SourcePattern] = &[
+    SourcePattern {
+        check: "no-std",
+        severity: Severity::Error,
+        pattern: "std",
+        token: true,
+        message: "`std` is not available in contracts, use `core` or `alloc`",
+    },
+    SourcePattern {
+        check: "float",
+        severity: Severity::Warning,
+        pattern: "f32",
+        token: true,
+        message: "floating point is emulated by software on CKB-VM and costs many cycles",
+    },
+    SourcePattern {
+        check: "float",
+        severity: Severity::Warning,
+        pattern: "f64",
+        token: true,
+        message: "floating point is emulated by software on CKB-VM and costs many cycles",
+    },
+    SourcePattern {
+        check: "release-debug",
+        severity: Severity::Warning,
+        pattern: "syscalls::debug(",
+        token: false,
+        message: "the debug syscall is kept in release builds, use `debug!` instead",
+    },
+];
+
+pub trait RustRecipe {
+    fn bin_name(name: &str) -> String;
+    /// `cargo new` option
+    fn cargo_new_opt() -> &'static str;
+    /// contract files and their templates
+    fn templates() -> &'static [(&'static str, &'static str)];
+    fn rustflags() -> Option<&'static str>;
+    /// file name of the cargo build output
+    fn cargo_output(name: &str) -> String;
+    /// command to produce the binary from the cargo build output
+    fn link_cmd(cargo_output: &str, bin: &str, build_env: BuildEnv) -> Option<String>;
+}
+
+pub struct RustBin;
 
-pub struct Rust {
+impl RustRecipe for RustBin {
+    fn bin_name(name: &str) -> String {
+        name.to_string()
+    }
+
+    fn cargo_new_opt() -> &'static str {
+        "--bin"
+    }
+
+    fn templates() -> &'static [(&'static str, &'static str)] {
+        &[
+            ("src/main.rs", "rust/contract/src/main.rs"),
+            ("src/error.rs", "rust/contract/src/error.rs"),
+            ("src/entry.rs", "rust/contract/src/entry.rs"),
+            ("Cargo.toml", "rust/contract/Cargo-manifest.toml"),
+        ]
+    }
+
+    fn rustflags() -> Option<&'static str> {
+        None
+    }
+
+    fn cargo_output(name: &str) -> String {
+        name.to_string()
+    }
+
+    fn link_cmd(_cargo_output: &str, _bin: &str, _build_env: BuildEnv) -> Option<String> {
+        None
+    }
+}
+
+pub struct RustSharedLib;
+
+impl RustRecipe for RustSharedLib {
+    fn bin_name(name: &str) -> String {
+        format!("{}.so", name)
+    }
+
+    fn cargo_new_opt() -> &'static str {
+        "--lib"
+    }
+
+    fn templates() -> &'static [(&'static str, &'static str)] {
+        &[
+            ("src/lib.rs", "rust/sharedlib/src/lib.rs"),
+            ("caller_example.rs", "rust/sharedlib/caller_example.rs"),
+            ("Cargo.toml", "rust/sharedlib/Cargo-manifest.toml"),
+        ]
+    }
+
+    fn rustflags() -> Option<&'static str> {
+        Some(SHARED_LIB_RUSTFLAGS)
+    }
+
+    fn cargo_output(name: &str) -> String {
+        format!("lib{}.a", name.replace('-', "_"))
+    }
+
+    /// the target doesn't support `cdylib`, link the static library into a shared library
+    fn link_cmd(cargo_output: &str, bin: &str, build_env: BuildEnv) -> Option<String> {
+        let link_flags = match build_env {
+            BuildEnv::Debug => SHARED_LIB_LINK_FLAGS.to_string(),
+            BuildEnv::Release => format!(
+                "{} {}",
+                SHARED_LIB_LINK_FLAGS, SHARED_LIB_RELEASE_LINK_FLAGS
+            ),
+        };
+        Some(format!(
+            "{gcc} -shared -nostdlib -fPIC {flags} -Wl,--whole-archive {lib} -Wl,--no-whole-archive -Wl,--gc-sections -o {bin}",
+            gcc = GCC_BIN,
+            flags = link_flags,
+            lib = cargo_output,
+            bin = bin
+        ))
+    }
+}
+
+pub struct Rust<R> {
     context: Context,
+    phantom_data: PhantomData<R>,
 }
 
-impl Rust {
+impl<R: RustRecipe> Rust<R> {
     pub fn new(context: Context) -> Self {
-        Self { context }
+        Self {
+            context,
+            phantom_data: PhantomData,
+        }
     }
 
     fn contract_path(&self, name: &str) -> PathBuf {
@@ -53,17 +205,21 @@ impl Rust {
     /// inject rustflags on release build unless project has cargo config
     fn injection_rustflags(&self, config: BuildConfig, name: &str) -> String {
         let has_cargo_config = self.has_cargo_config(name);
+        let base_rustflags = match R::rustflags() {
+            Some(rustflags) => format!("{} {}", BASE_RUSTFLAGS, rustflags),
+            None => BASE_RUSTFLAGS.to_string(),
+        };
         match config.build_env {
             _ if has_cargo_config => "".to_string(),
-            BuildEnv::Debug => format!("RUSTFLAGS=\"{}\"", BASE_RUSTFLAGS.to_string()),
+            BuildEnv::Debug => format!("RUSTFLAGS=\"{}\"", base_rustflags),
             BuildEnv::Release => {
                 if config.always_debug {
                     format!(
                         "RUSTFLAGS=\"{} {} {}\"",
-                        BASE_RUSTFLAGS, RELEASE_RUSTFLAGS, ALWAYS_DEBUG_RUSTFLAGS
+                        base_rustflags, RELEASE_RUSTFLAGS, ALWAYS_DEBUG_RUSTFLAGS
                     )
                 } else {
-                    format!("RUSTFLAGS=\"{} {}\"", BASE_RUSTFLAGS, RELEASE_RUSTFLAGS)
+                    format!("RUSTFLAGS=\"{} {}\"", base_rustflags, RELEASE_RUSTFLAGS)
                 }
             }
         }
@@ -84,7 +240,7 @@ impl Rust {
     }
 }
 
-impl Recipe for Rust {
+impl<R: RustRecipe> Recipe for Rust<R> {
     fn exists(&self, name: &str) -> bool {
         self.contract_path(name).exists()
     }
@@ -100,24 +256,22 @@ impl Recipe for Rust {
         let path = self.context.contracts_path();
         let context = tera::Context::from_serialize(&CreateContract { name: name.clone() })?;
         // generate contract
-        let cmd = DockerCommand::with_config(
+        let cmd = DockerCommand::with_context(
+            &self.context,
             DOCKER_IMAGE.to_string(),
             path.to_str().expect("str").to_string(),
         )
         .fix_dir_permission(name.clone());
-        cmd.run(format!("cargo new {} --vcs none", name), signal)?;
+        cmd.run(
+            format!("cargo new {} {} --vcs none", name, R::cargo_new_opt()),
+            signal,
+        )?;
         let mut contract_path = PathBuf::new();
         contract_path.push(path);
         contract_path.push(name);
         // initialize contract code
-        for (f, template_name) in &[
-            ("src/main.rs", None),
-            ("src/error.rs", None),
-            ("src/entry.rs", None),
-            ("Cargo.toml", Some("Cargo-manifest.toml")),
-        ] {
-            let template_path = format!("rust/contract/{}", template_name.unwrap_or(f));
-            let content = TEMPLATES.render(&template_path, &context)?;
+        for (f, template_path) in R::templates() {
+            let content = TEMPLATES.render(template_path, &context)?;
             let mut file_path = contract_path.clone();
             file_path.push(f);
             fs::write(file_path, content)?;
@@ -129,6 +283,49 @@ impl Recipe for Rust {
         Ok(())
     }
 
+    fn import_contract(
+        &self,
+        contract: &Contract,
+        source: &Path,
+        link: bool,
+        _signal: &Signal,
+    ) -> Result<()> {
+        let name = &contract.name;
+        let mut source_cargo_path = source.to_path_buf();
+        source_cargo_path.push(CARGO_CONFIG_FILE);
+        let doc = read_config_file(&source_cargo_path)?.parse::<Document>()?;
+        // binaries are named after the contract
+        let package_name = doc["package"]["name"].as_str().unwrap_or_default();
+        let has_workspace = doc.as_table().contains_key("workspace");
+        let contract_path = self.contract_path(name);
+        println!("Import contract {:?} from {:?}", name, source);
+        if link {
+            if package_name != name {
+                return Err(anyhow!(
+                    "package name '{}' is not the contract name, linked crate can't be renamed",
+                    package_name
+                ));
+            }
+            if has_workspace {
+                return Err(anyhow!(
+                    "{:?} is a workspace, linked crate can't be a member of the project workspace",
+                    source
+                ));
+            }
+            relative_symlink(source, &contract_path, &self.context.project_path)?;
+        } else {
+            copy_dir(source, &contract_path)?;
+            let mut cargo_path = contract_path.clone();
+            cargo_path.push(CARGO_CONFIG_FILE);
+            let mut doc = read_config_file(&cargo_path)?.parse::<Document>()?;
+            doc["package"]["name"] = value(name.as_str());
+            // nested workspace is not allowed
+            doc.as_table_mut().remove("workspace");
+            write_config_file(&cargo_path, doc.to_string())?;
+        }
+        self.rewrite_config_for_new_contract(name)
+    }
+
     /// run command in build image
     fn run(&self, contract: &Contract, build_cmd: String, signal: &Signal) -> Result<()> {
         let project_path = self.context.project_path.to_str().expect("path");
@@ -156,23 +353,33 @@ impl Recipe for Rust {
             BuildEnv::Debug => ("debug", ""),
             BuildEnv::Release => ("release", "--release"),
         };
-        rel_bin_path.push(format!(
-            "target/{}/{}/{}",
-            RUST_TARGET, bin_dir_prefix, &contract.name
-        ));
-        let mut container_bin_path = PathBuf::new();
-        container_bin_path.push("/code");
+        let rel_target_dir = format!("target/{}/{}", RUST_TARGET, bin_dir_prefix);
+        rel_bin_path.push(&rel_target_dir);
+        rel_bin_path.push(R::bin_name(&contract.name));
+        let mut container_target_dir = PathBuf::new();
+        container_target_dir.push("/code");
         if let Some(workspace_dir) = self.context.config.rust.workspace_dir.as_ref() {
-            container_bin_path.push(workspace_dir);
+            container_target_dir.push(workspace_dir);
         }
-        container_bin_path.push(&rel_bin_path);
+        container_target_dir.push(&rel_target_dir);
+        let container_bin_path = container_target_dir.join(R::bin_name(&contract.name));
+        let container_output_path = container_target_dir.join(R::cargo_output(&contract.name));
+        let link_cmd = match R::link_cmd(
+            container_output_path.to_str().expect("path"),
+            container_bin_path.to_str().expect("path"),
+            config.build_env,
+        ) {
+            Some(cmd) => format!("{} && ", cmd),
+            None => "".to_string(),
+        };
 
         // run build command
         let build_cmd = format!(
-            "{rustflags} cargo build --target {rust_target} {build_env} && \
+            "{rustflags} cargo build --target {rust_target} {build_env} && {link_cmd}\
          ckb-binary-patcher -i {contract_bin} -o {contract_bin}",
             rustflags = self.injection_rustflags(config, &contract.name),
             rust_target = RUST_TARGET,
+            link_cmd = link_cmd,
             contract_bin = container_bin_path.to_str().expect("bin"),
             build_env = build_cmd_opt
         );
@@ -187,10 +394,9 @@ impl Recipe for Rust {
         if !project_bin_path.exists() {
             return Err(anyhow!("can't find contract binary from path {:?}, please set `workspace_dir` in capsule.toml", project_bin_path));
         }
-        let mut target_path = self.context.contracts_build_path(config.build_env);
+        let target_path = self.binary_path(contract, config.build_env);
         // make sure the dir is exist
-        fs::create_dir_all(&target_path)?;
-        target_path.push(&contract.name);
+        fs::create_dir_all(&target_path.parent().ok_or(anyhow!("expect build dir"))?)?;
         fs::copy(project_bin_path, target_path)?;
         Ok(())
     }
@@ -208,10 +414,9 @@ impl Recipe for Rust {
 
             // remove binary
             for build_env in &[BuildEnv::Debug, BuildEnv::Release] {
-                let mut target_path = self.context.contracts_build_path(*build_env);
                 // make sure the dir is exist
-                fs::create_dir_all(&target_path)?;
-                target_path.push(&c.name);
+                fs::create_dir_all(&self.context.contracts_build_path(*build_env))?;
+                let target_path = self.binary_path(c, *build_env);
                 if target_path.exists() {
                     fs::remove_file(&target_path)?;
                 }
@@ -219,4 +424,172 @@ impl Recipe for Rust {
         }
         Ok(())
     }
+
+    fn source_paths(&self, contract: &Contract) -> Vec<PathBuf> {
+        vec![self.contract_path(&contract.name)]
+    }
+
+    fn binary_path(&self, contract: &Contract, build_env: BuildEnv) -> PathBuf {
+        let mut path = self.context.contracts_build_path(build_env);
+        path.push(R::bin_name(&contract.name));
+        path
+    }
+
+    fn debug_symbol_path(&self, contract: &Contract, build_env: BuildEnv) -> PathBuf {
+        self.binary_path(contract, build_env)
+    }
+
+    /// release binaries are stripped by the linker
+    fn unstripped_binary_path(&self, contract: &Contract, build_env: BuildEnv) -> Option<PathBuf> {
+        match build_env {
+            BuildEnv::Debug => Some(self.binary_path(contract, build_env)),
+            BuildEnv::Release => None,
+        }
+    }
+
+    fn code_dir(&self) -> PathBuf {
+        self.context.project_path.clone()
+    }
+
+    fn code_hashes_path(&self, contract: &Contract) -> PathBuf {
+        let mut path = self.contract_path(&contract.name);
+        path.push("src");
+        path.push(CODE_HASHES_FILE);
+        path
+    }
+
+    fn rename_contract(
+        &self,
+        contract: &Contract,
+        new_name: &str,
+        changes: &mut Changes,
+    ) -> Result<()> {
+        let mut cargo_path = self.context.project_path.clone();
+        cargo_path.push(CARGO_CONFIG_FILE);
+        let member = format!("{}/{}", CONTRACTS_DIR, contract.name);
+        let new_member = format!("{}/{}", CONTRACTS_DIR, new_name);
+        changes.edit_toml(&cargo_path, |doc| {
+            rename_cargo_workspace_member(doc, &member, &new_member)
+        })?;
+        let mut contract_cargo_path = self.contract_path(&contract.name);
+        contract_cargo_path.push(CARGO_CONFIG_FILE);
+        if contract_cargo_path.exists() {
+            changes.edit_toml(&contract_cargo_path, |doc| {
+                doc["package"]["name"] = value(new_name);
+                Ok(())
+            })?;
+        }
+        changes.move_path(
+            self.contract_path(&contract.name),
+            self.contract_path(new_name),
+        );
+        Ok(())
+    }
+
+    fn remove_contract(&self, contract: &Contract, changes: &mut Changes) -> Result<()> {
+        let mut cargo_path = self.context.project_path.clone();
+        cargo_path.push(CARGO_CONFIG_FILE);
+        let member = format!("{}/{}", CONTRACTS_DIR, contract.name);
+        changes.edit_toml(&cargo_path, |doc| {
+            remove_cargo_workspace_member(doc, &member)
+        })?;
+        changes.remove_path(self.contract_path(&contract.name));
+        Ok(())
+    }
+
+    fn lint(&self, contract: &Contract, signal: &Signal) -> Result<Vec<Finding>> {
+        let contract_path = self.contract_path(&contract.name);
+        let src_dir = contract_path.join("src");
+        let files = source_files(&src_dir, &["rs"])?;
+        let mut findings = scan_sources(&self.context, contract, &files, SOURCE_PATTERNS)?;
+
+        // entry of the contract
+        let entry = ["main.rs", "lib.rs"]
+            .iter()
+            .map(|file| src_dir.join(file))
+            .find(|path| path.exists());
+        if let Some(entry) = entry {
+            if !fs::read_to_string(&entry)?.contains("#![no_std]") {
+                findings.push(Finding::new(
+                    contract,
+                    Severity::Error,
+                    "no-std",
+                    Some(location(&self.context, &entry, None)),
+                    "missing `#![no_std]`".to_string(),
+                ));
+            }
+        }
+
+        // dependencies
+        let cargo_path = contract_path.join(CARGO_CONFIG_FILE);
+        if cargo_path.exists() {
+            let doc = read_config_file(&cargo_path)?.parse::<Document>()?;
+            if let Some(deps) = doc["dependencies"].as_table() {
+                for (name, _) in deps.iter() {
+                    if HEAVY_DEPENDENCIES.contains(&name) {
+                        findings.push(Finding::new(
+                            contract,
+                            Severity::Warning,
+                            "heavy-dependency",
+                            Some(location(&self.context, &cargo_path, None)),
+                            format!("`{}` is too heavy for contracts or requires `std`", name),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // debug output in release builds, `debug!` is kept if debug assertions are enabled
+        let mut workspace_cargo_path = self.context.project_path.clone();
+        if let Some(workspace_dir) = self.context.config.rust.workspace_dir.as_ref() {
+            workspace_cargo_path.push(workspace_dir);
+        }
+        workspace_cargo_path.push(CARGO_CONFIG_FILE);
+        if workspace_cargo_path.exists() {
+            let doc = read_config_file(&workspace_cargo_path)?.parse::<Document>()?;
+            if doc["profile"]["release"]["debug-assertions"].as_bool() == Some(true) {
+                findings.push(Finding::new(
+                    contract,
+                    Severity::Warning,
+                    "release-debug",
+                    Some(location(&self.context, &workspace_cargo_path, None)),
+                    "`debug-assertions` of the release profile keeps `debug!` output in release builds".to_string(),
+                ));
+            }
+        }
+        let cargo_config_path = contract_path.join(CARGO_CONFIG_PATH);
+        if cargo_config_path.exists()
+            && fs::read_to_string(&cargo_config_path)?.contains("debug_assertions")
+        {
+            findings.push(Finding::new(
+                contract,
+                Severity::Warning,
+                "release-debug",
+                Some(location(&self.context, &cargo_config_path, None)),
+                "rustflags keep `debug!` output in release builds, use `capsule build --release --debug-output` instead".to_string(),
+            ));
+        }
+
+        // clippy
+        let config = BuildConfig {
+            build_env: BuildEnv::Debug,
+            always_debug: false,
+        };
+        let clippy_cmd = format!(
+            "{rustflags} cargo clippy --target {rust_target} -- -D warnings {lints}",
+            rustflags = self.injection_rustflags(config, &contract.name),
+            rust_target = RUST_TARGET,
+            lints = CLIPPY_LINTS
+        );
+        if let Err(err) = self.run(contract, clippy_cmd, signal) {
+            findings.push(Finding::new(
+                contract,
+                Severity::Error,
+                "clippy",
+                None,
+                format!("clippy reported problems, see the output above ({})", err),
+            ));
+        }
+        Ok(findings)
+    }
 }
@@ -1,14 +1,29 @@
-use crate::project_context::{BuildEnv, Context};
+use crate::contract_deps::{build_contract, build_order, dependency_binaries};
+use crate::coverage::{Coverage, COVERAGE_DIR, COVERAGE_ENV_VAR};
+use crate::project_context::{BuildConfig, BuildEnv, Context};
+use crate::recipe::get_recipe;
 use crate::recipe::rust::DOCKER_IMAGE;
 use crate::signal::Signal;
 use crate::util::docker::DockerCommand;
-use anyhow::Result;
+use crate::util::fs::latest_modified;
+use anyhow::{anyhow, Result};
+
+/// build env of contracts which are loaded by the tests crate
+pub const TEST_ENV_VAR: &str = "CAPSULE_TEST_ENV";
+
+#[derive(Debug, Copy, Clone)]
+pub struct TestConfig {
+    pub build_env: BuildEnv,
+    pub rebuild: bool,
+    pub coverage: bool,
+}
 
-const TEST_ENV_VAR: &str = "CAPSULE_TEST_ENV";
 pub struct Tester;
 
 impl Tester {
-    pub fn run(project_context: &Context, env: BuildEnv, signal: &Signal) -> Result<()> {
+    pub fn run(project_context: &Context, config: TestConfig, signal: &Signal) -> Result<()> {
+        let env = config.build_env;
+        Self::prepare_binaries(project_context, env, config.rebuild, signal)?;
         let env_arg = match env {
             BuildEnv::Debug => "debug",
             BuildEnv::Release => "release",
@@ -18,17 +33,76 @@ impl Tester {
             .to_str()
             .expect("project path")
             .to_string();
-        let cmd =
+        let mut cmd =
             DockerCommand::with_context(project_context, DOCKER_IMAGE.to_string(), project_path)
                 .fix_dir_permission("target".to_string())
                 .fix_dir_permission("Cargo.lock".to_string());
+        let mut test_env = format!("{}={}", TEST_ENV_VAR, env_arg);
+        if config.coverage {
+            Coverage::new(project_context).reset()?;
+            cmd = cmd.fix_dir_permission(COVERAGE_DIR.to_string());
+            test_env.push_str(&format!(" {}=/code/{}", COVERAGE_ENV_VAR, COVERAGE_DIR));
+        }
         cmd.run(
-            format!(
-                "{}={} cargo test -p tests -- --nocapture",
-                TEST_ENV_VAR, env_arg
-            ),
+            format!("{} cargo test -p tests -- --nocapture", test_env),
             signal,
         )?;
         Ok(())
     }
+
+    /// Rebuild contracts whose binary is missing or older than the sources,
+    /// returns an error instead if `rebuild` is false
+    pub fn prepare_binaries(
+        project_context: &Context,
+        env: BuildEnv,
+        rebuild: bool,
+        signal: &Signal,
+    ) -> Result<()> {
+        let mut outdated = Vec::new();
+        // dependencies are checked first, a dependent is outdated if a dependency binary is newer
+        for contract in &build_order(&project_context.config.contracts, &[])? {
+            let recipe = get_recipe(project_context.clone(), contract.template_type)?;
+            let bin_modified = latest_modified(recipe.binary_path(contract, env))?;
+            let mut src_modified = None;
+            let dep_binaries = dependency_binaries(project_context, contract, env)?
+                .into_iter()
+                .map(|(_, path)| path);
+            for path in recipe
+                .source_paths(contract)
+                .into_iter()
+                .chain(dep_binaries)
+            {
+                let modified = latest_modified(path)?;
+                if modified > src_modified {
+                    src_modified = modified;
+                }
+            }
+            if bin_modified.is_some() && bin_modified >= src_modified {
+                continue;
+            }
+            if rebuild {
+                println!("Building outdated contract {}", contract.name);
+                let config = BuildConfig {
+                    build_env: env,
+                    always_debug: false,
+                };
+                build_contract(project_context, contract, config, signal)?;
+            } else {
+                outdated.push(contract.name.clone());
+            }
+        }
+        if !outdated.is_empty() {
+            let release_opt = match env {
+                BuildEnv::Debug => "",
+                BuildEnv::Release => " --release",
+            };
+            return Err(anyhow!(
+                "contracts binaries are missing or outdated: {}\nhint: run `capsule build{} -n {}`",
+                outdated.join(", "),
+                release_opt,
+                outdated.join(" -n ")
+            ));
+        }
+        Ok(())
+    }
 }
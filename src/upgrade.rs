@@ -0,0 +1,236 @@
+//! Upgrade projects created by older capsule
+//!
+//! Migrations are applied in version order to projects older than or equal to the migration version,
+//! so every migration must be idempotent. Changes are previewed and written after confirmation.
+
+use crate::config_manipulate::{set_c_dependency, value, Document};
+use crate::deps::{ckb_c_stdlib, CKB_C_STDLIB_NAME, DEPS_DIR};
+use crate::generator::TEMPLATES;
+use crate::project_context::CONFIG_FILE;
+use crate::signal::Signal;
+use crate::util::changes::Changes;
+use crate::version::{parse_version, version_string};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+const C_MAKEFILE: &str = "contracts/c/Makefile";
+const TESTS_LIB: &str = "tests/src/lib.rs";
+
+type Version = (u64, u64, u64);
+
+pub struct Migration {
+    pub version: Version,
+    pub description: &'static str,
+    apply: fn(&mut Upgrade) -> Result<()>,
+}
+
+/// migrations in version order
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: (0, 4, 3),
+        description: "Remove object files of multi-file C contracts in `make clean`",
+        apply: migrate_c_makefile_clean,
+    },
+    Migration {
+        version: (0, 4, 3),
+        description: "Update the C builder docker image",
+        apply: migrate_c_builder_docker,
+    },
+    Migration {
+        version: (0, 4, 3),
+        description: "Declare ckb-c-stdlib in `[[c_deps]]` of capsule.toml",
+        apply: migrate_c_deps,
+    },
+    Migration {
+        version: (0, 4, 3),
+        description: "Print build hint when tests `Loader` can't find a contract",
+        apply: migrate_tests_loader,
+    },
+];
+
+pub struct Upgrade {
+    changes: Changes,
+}
+
+impl Upgrade {
+    pub fn new<P: AsRef<Path>>(project_path: P) -> Self {
+        Upgrade {
+            changes: Changes::new(project_path),
+        }
+    }
+
+    fn read(&self, path: &str) -> Result<Option<String>> {
+        self.changes.read(path)
+    }
+
+    fn write(&mut self, path: &str, content: String) -> Result<()> {
+        self.changes.write(path, content)
+    }
+
+    fn edit_config<F: FnOnce(&mut Document) -> Result<()>>(&mut self, f: F) -> Result<()> {
+        self.changes.edit_toml(CONFIG_FILE, f)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.changes.exists(path)
+    }
+
+    fn note(&mut self, note: String) {
+        self.changes.note(note);
+    }
+
+    pub fn project_version(&self) -> Result<String> {
+        let content = self.read(CONFIG_FILE)?.ok_or_else(|| {
+            anyhow!(
+                "can't find {}, current directory is not a project",
+                CONFIG_FILE
+            )
+        })?;
+        let doc = content.parse::<Document>()?;
+        Ok(doc["version"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// apply migrations and update the project version, returns applied migrations
+    pub fn migrate(&mut self) -> Result<Vec<&'static Migration>> {
+        let project_version = self.project_version()?;
+        // projects without a version are created by the earliest capsule
+        let from = parse_version(&project_version).unwrap_or((0, 0, 0));
+        let mut applied = Vec::new();
+        for migration in MIGRATIONS.iter().filter(|m| m.version >= from) {
+            let changes = self.changes.count();
+            let notes = self.changes.notes().len();
+            (migration.apply)(self)?;
+            if self.changes.count() > changes || self.changes.notes().len() > notes {
+                applied.push(migration);
+            }
+        }
+        let version = version_string();
+        if project_version != version {
+            self.edit_config(|doc| {
+                doc["version"] = value(version);
+                Ok(())
+            })?;
+        }
+        Ok(applied)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// print changes as unified diff
+    pub fn diff(&self, signal: &Signal) -> Result<()> {
+        self.changes.preview(signal)
+    }
+
+    /// write changes into the project
+    pub fn apply(self) -> Result<()> {
+        self.changes.apply()
+    }
+}
+
+fn migrate_c_makefile_clean(upgrade: &mut Upgrade) -> Result<()> {
+    let makefile = match upgrade.read(C_MAKEFILE)? {
+        Some(makefile) => makefile,
+        None => return Ok(()),
+    };
+    let clean_objs = "\trm -rf build/debug/obj build/release/obj\n";
+    if makefile.contains(clean_objs) {
+        return Ok(());
+    }
+    let anchor = "\trm -rf build/*.o\n";
+    if !makefile.contains(anchor) {
+        upgrade.note(format!(
+            "{}: add `rm -rf build/debug/obj build/release/obj` to the clean target",
+            C_MAKEFILE
+        ));
+        return Ok(());
+    }
+    let makefile = makefile.replacen(anchor, &format!("{}{}", anchor, clean_objs), 1);
+    upgrade.write(C_MAKEFILE, makefile)
+}
+
+fn migrate_c_builder_docker(upgrade: &mut Upgrade) -> Result<()> {
+    let makefile = match upgrade.read(C_MAKEFILE)? {
+        Some(makefile) => makefile,
+        None => return Ok(()),
+    };
+    let template = TEMPLATES.render("c/Makefile", &tera::Context::default())?;
+    let builder_line = |content: &str| {
+        content
+            .lines()
+            .find(|line| line.starts_with("BUILDER_DOCKER"))
+            .map(|line| line.to_string())
+    };
+    let (current, latest) = match (builder_line(&makefile), builder_line(&template)) {
+        (Some(current), Some(latest)) => (current, latest),
+        _ => return Ok(()),
+    };
+    if current != latest {
+        let makefile = makefile.replacen(&current, &latest, 1);
+        upgrade.write(C_MAKEFILE, makefile)?;
+    }
+    Ok(())
+}
+
+fn migrate_c_deps(upgrade: &mut Upgrade) -> Result<()> {
+    let stdlib_dir = format!("{}/{}", DEPS_DIR, CKB_C_STDLIB_NAME);
+    if !upgrade.exists(&stdlib_dir) {
+        return Ok(());
+    }
+    let content = upgrade.read(CONFIG_FILE)?.unwrap_or_default();
+    let doc = content.parse::<Document>()?;
+    let declared = doc["c_deps"]
+        .as_array_of_tables()
+        .map(|deps| {
+            deps.iter()
+                .any(|dep| dep["name"].as_str() == Some(CKB_C_STDLIB_NAME))
+        })
+        .unwrap_or(false);
+    if declared {
+        return Ok(());
+    }
+    upgrade.edit_config(|doc| set_c_dependency(doc, &ckb_c_stdlib()))?;
+    if upgrade.exists(".gitmodules") {
+        upgrade.note(format!(
+            "{} is a git submodule, remove it by `git rm {}` and run `capsule deps fetch`",
+            stdlib_dir, stdlib_dir
+        ));
+    }
+    Ok(())
+}
+
+fn migrate_tests_loader(upgrade: &mut Upgrade) -> Result<()> {
+    let lib = match upgrade.read(TESTS_LIB)? {
+        Some(lib) => lib,
+        None => return Ok(()),
+    };
+    let old = r#"    pub fn load_binary(&self, name: &str) -> Bytes {
+        let mut path = self.0.clone();
+        path.push(name);
+        fs::read(path).expect("binary").into()
+    }"#;
+    let new = r#"    pub fn try_load_binary(&self, name: &str) -> Result<Bytes, String> {
+        let mut path = self.0.clone();
+        path.push(name);
+        let release_opt = if self.0.ends_with("release") {
+            " --release"
+        } else {
+            ""
+        };
+        fs::read(&path).map(Into::into).map_err(|err| {
+            format!(
+                "failed to load contract '{}' from {:?}: {}\nhint: run `capsule build{} -n {}` first",
+                name, path, err, release_opt, name
+            )
+        })
+    }
+
+    pub fn load_binary(&self, name: &str) -> Bytes {
+        self.try_load_binary(name).unwrap_or_else(|err| panic!("{}", err))
+    }"#;
+    if lib.contains(old) {
+        upgrade.write(TESTS_LIB, lib.replacen(old, new, 1))?;
+    }
+    Ok(())
+}
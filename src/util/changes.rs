@@ -0,0 +1,218 @@
+//! Pending changes of project files
+//!
+//! Edits are kept in memory, previewed as a unified diff and written at once.
+
+use crate::config_manipulate::Document;
+use crate::signal::Signal;
+use crate::util::cli;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CHANGES_TMP_DIR: &str = ".tmp/changes";
+
+pub struct Changes {
+    project_path: PathBuf,
+    // relative path => (original content, new content), None is a deleted file
+    files: BTreeMap<PathBuf, (Option<String>, Option<String>)>,
+    // relative paths, applied after file edits
+    moves: Vec<(PathBuf, PathBuf)>,
+    removals: Vec<PathBuf>,
+    // manual steps which can't be done automatically
+    notes: Vec<String>,
+    // number of effective edits
+    count: usize,
+}
+
+impl Changes {
+    pub fn new<P: AsRef<Path>>(project_path: P) -> Self {
+        Changes {
+            project_path: project_path.as_ref().to_path_buf(),
+            files: BTreeMap::new(),
+            moves: Vec::new(),
+            removals: Vec::new(),
+            notes: Vec::new(),
+            count: 0,
+        }
+    }
+
+    pub fn project_path(&self) -> &Path {
+        &self.project_path
+    }
+
+    /// paths are relative to the project, absolute paths under the project are accepted
+    fn rel_path(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.project_path)
+            .unwrap_or(path)
+            .to_path_buf()
+    }
+
+    /// content of a project file, includes pending edits
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Option<String>> {
+        let path = &self.rel_path(path.as_ref());
+        if let Some((_, content)) = self.files.get(path) {
+            return Ok(content.clone());
+        }
+        let file_path = self.project_path.join(path);
+        if !file_path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(file_path)?))
+    }
+
+    fn edit(&mut self, path: &Path, content: Option<String>) -> Result<()> {
+        let path = &self.rel_path(path);
+        if path.is_absolute() {
+            return Err(anyhow!("{:?} is not under the project", path));
+        }
+        if self.read(path)? == content {
+            return Ok(());
+        }
+        let original = match self.files.remove(path) {
+            Some((original, _)) => original,
+            None => self.read(path)?,
+        };
+        self.files.insert(path.to_path_buf(), (original, content));
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn write<P: AsRef<Path>>(&mut self, path: P, content: String) -> Result<()> {
+        self.edit(path.as_ref(), Some(content))
+    }
+
+    pub fn delete<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.edit(path.as_ref(), None)
+    }
+
+    /// edit a toml file with toml_edit, formats and comments are kept
+    pub fn edit_toml<P, F>(&mut self, path: P, f: F) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&mut Document) -> Result<()>,
+    {
+        let path = path.as_ref();
+        let content = self
+            .read(path)?
+            .ok_or_else(|| anyhow!("can't find {:?}", path))?;
+        let mut doc = content.parse::<Document>()?;
+        f(&mut doc)?;
+        self.write(path, doc.to_string())
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.project_path.join(path).exists()
+    }
+
+    /// move a file or dir, ignored if it doesn't exist
+    pub fn move_path<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) {
+        if self.exists(&from) {
+            let from = self.rel_path(from.as_ref());
+            let to = self.rel_path(to.as_ref());
+            self.moves.push((from, to));
+            self.count += 1;
+        }
+    }
+
+    /// remove a file or dir, ignored if it doesn't exist
+    pub fn remove_path<P: AsRef<Path>>(&mut self, path: P) {
+        if self.exists(&path) {
+            let path = self.rel_path(path.as_ref());
+            self.removals.push(path);
+            self.count += 1;
+        }
+    }
+
+    pub fn note(&mut self, note: String) {
+        self.notes.push(note);
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0 && self.notes.is_empty()
+    }
+
+    pub fn changed_files(&self) -> Vec<&Path> {
+        self.files.keys().map(|path| path.as_path()).collect()
+    }
+
+    /// print moves, removals and unified diff of file edits
+    pub fn preview(&self, signal: &Signal) -> Result<()> {
+        for (from, to) in &self.moves {
+            println!("move {:?} -> {:?}", from, to);
+        }
+        for path in &self.removals {
+            println!("remove {:?}", path);
+        }
+        if self.files.is_empty() {
+            for note in &self.notes {
+                println!("note: {}", note);
+            }
+            return Ok(());
+        }
+        let tmp_dir = self.project_path.join(CHANGES_TMP_DIR);
+        if tmp_dir.exists() {
+            fs::remove_dir_all(&tmp_dir)?;
+        }
+        for (path, (original, content)) in &self.files {
+            for (side, content) in &[("a", original), ("b", content)] {
+                let file_path = tmp_dir.join(side).join(path);
+                fs::create_dir_all(file_path.parent().expect("parent dir"))?;
+                if let Some(content) = content {
+                    fs::write(file_path, content)?;
+                }
+            }
+        }
+        // diff exits with 1 if files are different
+        let result = cli::run("diff -ruN a b || true".to_string(), &tmp_dir, signal);
+        fs::remove_dir_all(&tmp_dir)?;
+        result?;
+        for note in &self.notes {
+            println!("note: {}", note);
+        }
+        Ok(())
+    }
+
+    /// write edits, then move and remove paths
+    pub fn apply(self) -> Result<()> {
+        for (path, (_, content)) in self.files {
+            let file_path = self.project_path.join(&path);
+            match content {
+                Some(content) => {
+                    fs::write(file_path, content)?;
+                    println!("Rewrite {:?}", path);
+                }
+                None => {
+                    fs::remove_file(file_path)?;
+                    println!("Removed {:?}", path);
+                }
+            }
+        }
+        for (from, to) in self.moves {
+            let to_path = self.project_path.join(&to);
+            if let Some(dir) = to_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::rename(self.project_path.join(&from), to_path)?;
+            println!("Moved {:?} -> {:?}", from, to);
+        }
+        for path in self.removals {
+            let full_path = self.project_path.join(&path);
+            if full_path.is_dir() {
+                fs::remove_dir_all(full_path)?;
+            } else if full_path.exists() {
+                fs::remove_file(full_path)?;
+            }
+            println!("Removed {:?}", path);
+        }
+        Ok(())
+    }
+}
@@ -14,6 +14,11 @@ pub fn ask_for_confirm(msg: &str) -> Result<bool> {
     Ok(["y", "yes"].contains(&buf.trim().to_lowercase().as_str()))
 }
 
+/// quote an argument of the shell command
+pub fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
 pub fn run<P: AsRef<Path>>(shell_cmd: String, workdir: P, signal: &Signal) -> Result<()> {
     debug!("Run command: {}", shell_cmd);
     let mut cmd = Command::new("sh");
@@ -1,3 +1,4 @@
+use crate::cache::CargoCache;
 use crate::project_context::Context;
 use crate::signal::Signal;
 use anyhow::{anyhow, Result};
@@ -25,7 +26,7 @@ pub struct DockerCommand {
     user: String,
     docker_image: String,
     code_path: String,
-    patch_cargo_cache: bool,
+    cargo_cache: Option<CargoCache>,
     fix_permission_files: Vec<String>,
     mapping_ports: Vec<Port>,
     mapping_volumes: Vec<Volume>,
@@ -38,8 +39,8 @@ pub struct DockerCommand {
 }
 
 impl DockerCommand {
-    pub fn with_context(_context: &Context, docker_image: String, code_path: String) -> Self {
-        Self::with_config(docker_image, code_path)
+    pub fn with_context(context: &Context, docker_image: String, code_path: String) -> Self {
+        Self::with_config(docker_image, code_path).cargo_cache(CargoCache::for_project(context))
     }
 
     pub fn with_config(docker_image: String, code_path: String) -> Self {
@@ -58,7 +59,7 @@ impl DockerCommand {
             user,
             docker_image,
             code_path,
-            patch_cargo_cache: true,
+            cargo_cache: Some(CargoCache::shared()),
             fix_permission_files: Vec::new(),
             mapping_ports: Vec::new(),
             mapping_volumes: Vec::new(),
@@ -71,6 +72,11 @@ impl DockerCommand {
         }
     }
 
+    pub fn cargo_cache(mut self, cache: CargoCache) -> Self {
+        self.cargo_cache = Some(cache);
+        self
+    }
+
     pub fn host_network(mut self, enable: bool) -> Self {
         self.host_network = enable;
         self
@@ -155,10 +161,10 @@ impl DockerCommand {
             user,
             docker_image,
             code_path,
-            patch_cargo_cache,
+            cargo_cache,
             fix_permission_files,
             mapping_ports,
-            mut mapping_volumes,
+            mapping_volumes,
             host_network,
             name,
             daemon,
@@ -178,13 +184,9 @@ impl DockerCommand {
             format!("-w{}", workdir).as_str(),
         ]);
 
-        // reusing cargo cache
-        // mapping local volume `capsule-cache` to reusing cargo cache
-        if patch_cargo_cache {
-            mapping_volumes.push(Volume {
-                volume: "capsule-cache".to_string(),
-                container: "/root/.cargo".to_string(),
-            });
+        // reusing cargo cache, a shared volume, a project volume or a host dir
+        if let Some(cargo_cache) = cargo_cache {
+            cmd.args(cargo_cache.volume_args()?);
         }
 
         // mapping volumes
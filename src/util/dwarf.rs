@@ -0,0 +1,128 @@
+//! DWARF line table of contract binaries
+
+use crate::project_context::Context;
+use crate::recipe::rust::DOCKER_IMAGE;
+use crate::signal::Signal;
+use crate::util::docker::DockerCommand;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+const OBJDUMP_BIN: &str = "riscv64-unknown-elf-objdump";
+const TMP_DIR: &str = ".tmp";
+
+/// Row of the DWARF line table
+#[derive(Debug, Clone)]
+pub struct LineRow {
+    pub address: u64,
+    pub file: String,
+    pub line: u32,
+}
+
+/// Line table sorted by address
+pub struct LineTable {
+    rows: Vec<LineRow>,
+}
+
+impl LineTable {
+    /// dump the line table of the binary in the build image
+    pub fn load<P: AsRef<Path>>(
+        context: &Context,
+        symbol_path: P,
+        signal: &Signal,
+    ) -> Result<Self> {
+        let symbol_path = symbol_path.as_ref();
+        if !symbol_path.exists() {
+            return Err(anyhow!("can't find debug symbols at {:?}", symbol_path));
+        }
+        let rel_symbol_path = symbol_path
+            .strip_prefix(&context.project_path)
+            .map_err(|_| anyhow!("debug symbols must be under the project dir"))?;
+        let file_name = symbol_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("file name");
+        let output_name = format!("{}.lines", file_name);
+        let project_path = context.project_path.to_str().expect("path");
+        let mut output_path = context.project_path.clone();
+        output_path.push(TMP_DIR);
+        fs::create_dir_all(&output_path)?;
+        output_path.push(&output_name);
+        DockerCommand::with_context(context, DOCKER_IMAGE.to_string(), project_path.to_string())
+            .fix_dir_permission(TMP_DIR.to_string())
+            .run(
+                format!(
+                    "{objdump} --dwarf=decodedline {symbol} > {dir}/{output}",
+                    objdump = OBJDUMP_BIN,
+                    symbol = rel_symbol_path.to_str().expect("path"),
+                    dir = TMP_DIR,
+                    output = output_name
+                ),
+                signal,
+            )?;
+        let content = fs::read_to_string(&output_path)?;
+        fs::remove_file(&output_path)?;
+        Ok(Self::parse(&content))
+    }
+
+    /// parse output of `objdump --dwarf=decodedline`
+    ///
+    /// ```text
+    /// CU: ./src/main.rs:
+    /// File name            Line number    Starting address    View    Stmt
+    /// main.rs                       12             0x100b0               x
+    /// ```
+    pub fn parse(content: &str) -> Self {
+        let mut rows = Vec::new();
+        let mut cu_path = String::new();
+        for line in content.lines() {
+            if let Some(path) = line.strip_prefix("CU: ") {
+                cu_path = path.trim_end_matches(':').to_string();
+                continue;
+            }
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() < 3 {
+                continue;
+            }
+            let line_number = match columns[1].parse::<u32>() {
+                Ok(n) => n,
+                Err(_) => continue,
+            };
+            let address = match columns[2]
+                .strip_prefix("0x")
+                .and_then(|addr| u64::from_str_radix(addr, 16).ok())
+            {
+                Some(addr) => addr,
+                None => continue,
+            };
+            // objdump prints file names without dir, use the CU path when it matches
+            let file_name = columns[0];
+            let file =
+                if Path::new(&cu_path).file_name().and_then(|n| n.to_str()) == Some(file_name) {
+                    cu_path.clone()
+                } else {
+                    file_name.to_string()
+                };
+            rows.push(LineRow {
+                address,
+                file,
+                line: line_number,
+            });
+        }
+        rows.sort_by_key(|row| row.address);
+        LineTable { rows }
+    }
+
+    pub fn rows(&self) -> &[LineRow] {
+        &self.rows
+    }
+
+    /// the row with the greatest address not above pc
+    pub fn lookup(&self, pc: u64) -> Option<&LineRow> {
+        match self.rows.binary_search_by_key(&pc, |row| row.address) {
+            Ok(i) => Some(&self.rows[i]),
+            Err(0) => None,
+            Err(i) => Some(&self.rows[i - 1]),
+        }
+    }
+}
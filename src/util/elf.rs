@@ -0,0 +1,420 @@
+//! Validation of contract ELF binaries
+//!
+//! Only headers, program headers, section headers and dynamic relocations are parsed,
+//! which is enough to check that CKB-VM can load the binary.
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EM_RISCV: u16 = 243;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+const SHT_RISCV_ATTRIBUTES: u32 = 0x7000_0003;
+const SHF_ALLOC: u64 = 2;
+
+const EF_RISCV_FLOAT_ABI: u32 = 0x6;
+const EF_RISCV_RVE: u32 = 0x8;
+
+const R_RISCV_RELATIVE: u32 = 3;
+const R_RISCV_JUMP_SLOT: u32 = 5;
+
+/// extensions supported by CKB-VM, `a` is accepted since the rust target enables it
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "i", "m", "a", "c", "zicsr", "zifencei", "zmmul", "zba", "zbb", "zbc", "zbs",
+];
+
+struct ProgramHeader {
+    p_type: u32,
+    p_flags: u32,
+    p_vaddr: u64,
+    p_memsz: u64,
+}
+
+struct SectionHeader {
+    name: String,
+    sh_type: u32,
+    sh_flags: u64,
+    sh_offset: u64,
+    sh_size: u64,
+}
+
+/// little endian reader with bounds checks
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: u64, len: u64) -> Result<&'a [u8]> {
+        let start = offset as usize;
+        let end = offset
+            .checked_add(len)
+            .map(|end| end as usize)
+            .filter(|end| *end <= self.0.len())
+            .ok_or_else(|| anyhow!("truncated file, offset {:#x} is out of bounds", offset))?;
+        Ok(&self.0[start..end])
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16> {
+        let mut buf = [0u8; 2];
+        buf.copy_from_slice(self.bytes(offset, 2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.bytes(offset, 4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.bytes(offset, 8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+/// Result of the validation
+#[derive(Debug, Default)]
+pub struct ElfReport {
+    /// problems which prevent CKB-VM from loading or running the binary
+    pub problems: Vec<String>,
+    /// the binary has no symbol table and debug sections
+    pub stripped: bool,
+}
+
+pub fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(ELF_MAGIC)
+}
+
+/// extensions of a RISC-V arch string, e.g. `rv64i2p0_m2p0_a2p0_c2p0` or `rv64imac`
+fn arch_extensions(arch: &str) -> Vec<String> {
+    let mut extensions = Vec::new();
+    let arch = arch.trim_start_matches("rv64").trim_start_matches("rv32");
+    for (i, part) in arch.split('_').enumerate() {
+        if i > 0 && (part.starts_with('z') || part.starts_with('x') || part.starts_with('s')) {
+            let name: String = part
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+            extensions.push(name);
+            continue;
+        }
+        // single letter extensions with optional versions, e.g. `i2p0`
+        let mut prev_digit = false;
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                prev_digit = true;
+                continue;
+            }
+            if !(c == 'p' && prev_digit) {
+                extensions.push(c.to_string());
+            }
+            prev_digit = false;
+        }
+    }
+    extensions
+}
+
+fn parse(data: &[u8], report: &mut ElfReport) -> Result<()> {
+    let reader = Reader(data);
+    let ident = reader.bytes(0, 16)?;
+    if !is_elf(ident) {
+        return Err(anyhow!("not an ELF file"));
+    }
+    if ident[4] != ELFCLASS64 || ident[5] != ELFDATA2LSB {
+        return Err(anyhow!(
+            "not a 64-bit little endian ELF, CKB-VM only loads RV64 binaries"
+        ));
+    }
+    let e_type = reader.u16(16)?;
+    let e_machine = reader.u16(18)?;
+    let e_entry = reader.u64(24)?;
+    let e_phoff = reader.u64(32)?;
+    let e_shoff = reader.u64(40)?;
+    let e_flags = reader.u32(48)?;
+    let e_phentsize = reader.u16(54)? as u64;
+    let e_phnum = reader.u16(56)? as u64;
+    let e_shentsize = reader.u16(58)? as u64;
+    let e_shnum = reader.u16(60)? as u64;
+    let e_shstrndx = reader.u16(62)? as u64;
+
+    if e_machine != EM_RISCV {
+        return Err(anyhow!(
+            "machine is {} instead of RISC-V ({}), the binary is built for another target",
+            e_machine,
+            EM_RISCV
+        ));
+    }
+    if e_type != ET_EXEC && e_type != ET_DYN {
+        report.problems.push(format!(
+            "ELF type {} is neither an executable nor a shared library",
+            e_type
+        ));
+    }
+    if e_flags & EF_RISCV_FLOAT_ABI != 0 {
+        report.problems.push(
+            "uses a hardware floating point ABI, CKB-VM has no floating point registers"
+                .to_string(),
+        );
+    }
+    if e_flags & EF_RISCV_RVE != 0 {
+        report
+            .problems
+            .push("built for RV64E, CKB-VM requires the base integer ISA RV64I".to_string());
+    }
+
+    // segments
+    let mut segments = Vec::new();
+    for i in 0..e_phnum {
+        let offset = e_phoff.saturating_add(i.saturating_mul(e_phentsize));
+        segments.push(ProgramHeader {
+            p_type: reader.u32(offset)?,
+            p_flags: reader.u32(offset + 4)?,
+            p_vaddr: reader.u64(offset + 16)?,
+            p_memsz: reader.u64(offset + 40)?,
+        });
+    }
+    for segment in &segments {
+        if segment.p_type == PT_INTERP {
+            report.problems.push(
+                "requests a program interpreter, contracts must be linked statically".to_string(),
+            );
+        }
+        if segment.p_type == PT_LOAD && segment.p_flags & PF_W != 0 && segment.p_flags & PF_X != 0 {
+            report.problems.push(format!(
+                "segment at {:#x} is both writable and executable, link with `-zseparate-code`",
+                segment.p_vaddr
+            ));
+        }
+    }
+    let entry_is_executable = segments.iter().any(|segment| {
+        segment.p_type == PT_LOAD
+            && segment.p_flags & PF_X != 0
+            && e_entry >= segment.p_vaddr
+            && e_entry < segment.p_vaddr.saturating_add(segment.p_memsz)
+    });
+    // shared libraries are called by symbols, they may have no entry point
+    if !(e_type == ET_DYN && e_entry == 0) && !entry_is_executable {
+        report.problems.push(format!(
+            "entry point {:#x} is not in an executable segment",
+            e_entry
+        ));
+    }
+
+    // sections
+    let mut sections = Vec::new();
+    for i in 0..e_shnum {
+        let offset = e_shoff.saturating_add(i.saturating_mul(e_shentsize));
+        sections.push((
+            reader.u32(offset)?,
+            SectionHeader {
+                name: String::new(),
+                sh_type: reader.u32(offset + 4)?,
+                sh_flags: reader.u64(offset + 8)?,
+                sh_offset: reader.u64(offset + 24)?,
+                sh_size: reader.u64(offset + 32)?,
+            },
+        ));
+    }
+    let names = match sections.get(e_shstrndx as usize) {
+        Some((_, strtab)) => reader.bytes(strtab.sh_offset, strtab.sh_size)?,
+        None => &[][..],
+    };
+    let sections: Vec<_> = sections
+        .into_iter()
+        .map(|(name_offset, mut section)| {
+            let name = names.get(name_offset as usize..).unwrap_or_default();
+            let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+            section.name = String::from_utf8_lossy(&name[..end]).to_string();
+            section
+        })
+        .collect();
+
+    for section in &sections {
+        match section.sh_type {
+            SHT_RISCV_ATTRIBUTES => {
+                let content = reader.bytes(section.sh_offset, section.sh_size)?;
+                let content = String::from_utf8_lossy(content);
+                if let Some(start) = content.find("rv64") {
+                    let arch: String = content[start..]
+                        .chars()
+                        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                        .collect();
+                    let unsupported: Vec<_> = arch_extensions(&arch)
+                        .into_iter()
+                        .filter(|ext| !SUPPORTED_EXTENSIONS.contains(&ext.as_str()))
+                        .collect();
+                    if !unsupported.is_empty() {
+                        report.problems.push(format!(
+                            "arch {} uses extensions unsupported by CKB-VM: {}",
+                            arch,
+                            unsupported.join(", ")
+                        ));
+                    }
+                }
+            }
+            SHT_RELA | SHT_REL if section.sh_flags & SHF_ALLOC != 0 && section.sh_size > 0 => {
+                // dynamic relocations are applied by the loader
+                if e_type == ET_EXEC {
+                    report.problems.push(format!(
+                        "section {} contains dynamic relocations, CKB-VM doesn't relocate executables",
+                        section.name
+                    ));
+                    continue;
+                }
+                let entry_size = if section.sh_type == SHT_RELA { 24 } else { 16 };
+                let entries = Reader(reader.bytes(section.sh_offset, section.sh_size)?);
+                let mut unsupported = Vec::new();
+                for i in 0..section.sh_size / entry_size {
+                    let r_info = entries.u64(i * entry_size + 8)?;
+                    let r_type = (r_info & 0xffff_ffff) as u32;
+                    if r_type != R_RISCV_RELATIVE
+                        && r_type != R_RISCV_JUMP_SLOT
+                        && !unsupported.contains(&r_type)
+                    {
+                        unsupported.push(r_type);
+                    }
+                }
+                if !unsupported.is_empty() {
+                    let types: Vec<_> = unsupported.iter().map(|t| t.to_string()).collect();
+                    report.problems.push(format!(
+                        "section {} contains relocation types unsupported by dynamic loading: {}",
+                        section.name,
+                        types.join(", ")
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    report.stripped = !sections
+        .iter()
+        .any(|section| section.sh_type == SHT_SYMTAB || section.name.starts_with(".debug_"));
+    Ok(())
+}
+
+/// validate the binary, parse errors are reported as problems
+pub fn validate(data: &[u8]) -> ElfReport {
+    let mut report = ElfReport::default();
+    if let Err(err) = parse(data, &mut report) {
+        report.problems.push(err.to_string());
+    }
+    report
+}
+
+/// validate a contract binary, `require_stripped` is set for release builds
+pub fn check_binary<P: AsRef<Path>>(path: P, require_stripped: bool) -> Result<()> {
+    let path = path.as_ref();
+    let data = fs::read(path)?;
+    let mut report = validate(&data);
+    if require_stripped && !report.stripped {
+        report.problems.push(
+            "contains symbols or debug info, release binaries must be stripped to save capacity"
+                .to_string(),
+        );
+    }
+    if report.problems.is_empty() {
+        return Ok(());
+    }
+    let problems: Vec<_> = report
+        .problems
+        .iter()
+        .map(|problem| format!("  - {}", problem))
+        .collect();
+    Err(anyhow!(
+        "invalid contract binary {:?}:\n{}",
+        path,
+        problems.join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PF_R: u32 = 4;
+    const ENTRY: u64 = 0x10000;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// an RV64 executable with one loadable segment which contains the entry
+    fn elf(p_flags: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 64 + 56];
+        put(&mut data, 0, ELF_MAGIC);
+        data[4] = ELFCLASS64;
+        data[5] = ELFDATA2LSB;
+        data[6] = 1;
+        put(&mut data, 16, &ET_EXEC.to_le_bytes());
+        put(&mut data, 18, &EM_RISCV.to_le_bytes());
+        put(&mut data, 20, &1u32.to_le_bytes());
+        put(&mut data, 24, &ENTRY.to_le_bytes());
+        put(&mut data, 32, &64u64.to_le_bytes());
+        put(&mut data, 52, &64u16.to_le_bytes());
+        put(&mut data, 54, &56u16.to_le_bytes());
+        put(&mut data, 56, &1u16.to_le_bytes());
+        // program header
+        put(&mut data, 64, &PT_LOAD.to_le_bytes());
+        put(&mut data, 68, &p_flags.to_le_bytes());
+        put(&mut data, 80, &ENTRY.to_le_bytes());
+        put(&mut data, 88, &ENTRY.to_le_bytes());
+        put(&mut data, 96, &120u64.to_le_bytes());
+        put(&mut data, 104, &0x1000u64.to_le_bytes());
+        put(&mut data, 112, &0x1000u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_validate() {
+        let report = validate(&elf(PF_R | PF_X));
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert!(report.stripped);
+    }
+
+    #[test]
+    fn test_validate_writable_code() {
+        let report = validate(&elf(PF_R | PF_W | PF_X));
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].contains("writable and executable"));
+
+        let report = validate(&elf(PF_R | PF_W));
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].contains("entry point"));
+    }
+
+    #[test]
+    fn test_validate_invalid_files() {
+        assert_eq!(validate(b"not an elf").problems.len(), 1);
+        let mut data = elf(PF_R | PF_X);
+        data.truncate(100);
+        assert_eq!(validate(&data).problems.len(), 1);
+        let mut data = elf(PF_R | PF_X);
+        put(&mut data, 18, &62u16.to_le_bytes());
+        assert!(validate(&data).problems[0].contains("RISC-V"));
+    }
+
+    #[test]
+    fn test_arch_extensions() {
+        assert_eq!(
+            arch_extensions("rv64i2p0_m2p0_a2p0_c2p0_zba1p0"),
+            vec!["i", "m", "a", "c", "zba"]
+        );
+        assert_eq!(arch_extensions("rv64imac"), vec!["i", "m", "a", "c"]);
+        assert_eq!(
+            arch_extensions("rv64imafdc"),
+            vec!["i", "m", "a", "f", "d", "c"]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+// Dirs skipped while walking sources
+const IGNORE_DIRS: &[&str] = &["target", ".git"];
+
+/// Returns the latest modified time of a file or a directory tree,
+/// returns None if the path doesn't exist
+pub fn latest_modified<P: AsRef<Path>>(path: P) -> Result<Option<SystemTime>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(Some(metadata.modified()?));
+    }
+    let mut latest = None;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if IGNORE_DIRS.contains(&name.to_str().unwrap_or_default()) {
+            continue;
+        }
+        let modified = latest_modified(entry.path())?;
+        if modified > latest {
+            latest = modified;
+        }
+    }
+    Ok(latest)
+}
+
+/// Write the file only if the content is changed, so the file isn't treated as modified
+pub fn write_if_changed<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    if fs::read(path).ok().as_deref() != Some(content) {
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Copy a directory tree, ignored dirs are skipped
+pub fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if IGNORE_DIRS.contains(&name.to_str().unwrap_or_default()) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, to.join(&name))?;
+        } else {
+            fs::copy(&path, to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Create a relative symlink at `link` to `target`,
+/// both must be under `base_dir`, so the link still works in the build image
+pub fn relative_symlink<P, Q, B>(target: P, link: Q, base_dir: B) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    B: AsRef<Path>,
+{
+    let base_dir = base_dir.as_ref().canonicalize()?;
+    let target = target.as_ref().canonicalize()?;
+    let link = link.as_ref();
+    let link_dir = link
+        .parent()
+        .ok_or(anyhow!("invalid link path {:?}", link))?;
+    fs::create_dir_all(link_dir)?;
+    let link_dir = link_dir.canonicalize()?;
+    let (target_rel, link_dir_rel) = match (
+        target.strip_prefix(&base_dir),
+        link_dir.strip_prefix(&base_dir),
+    ) {
+        (Ok(target_rel), Ok(link_dir_rel)) => (target_rel, link_dir_rel),
+        _ => {
+            return Err(anyhow!(
+                "{:?} is not under {:?}, it can't be linked",
+                target,
+                base_dir
+            ))
+        }
+    };
+    let mut relative = PathBuf::new();
+    for component in link_dir_rel.components() {
+        if let Component::Normal(_) = component {
+            relative.push("..");
+        }
+    }
+    relative.push(target_rel);
+    std::os::unix::fs::symlink(relative, link)?;
+    Ok(())
+}
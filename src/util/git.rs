@@ -42,3 +42,50 @@ pub fn add_submodule(context: &Context, url: &str, rel_path: &str, commit_id: &s
         .status()?;
     wait(status)
 }
+
+pub fn clone<P: AsRef<Path>>(url: &str, dir: P) -> Result<()> {
+    let status = Command::new(GIT_BIN)
+        .arg("clone")
+        .arg("--quiet")
+        .arg(url)
+        .arg(dir.as_ref())
+        .status()?;
+    wait(status)
+}
+
+pub fn checkout<P: AsRef<Path>>(dir: P, revision: &str) -> Result<()> {
+    let status = Command::new(GIT_BIN)
+        .arg("checkout")
+        .arg("--quiet")
+        .arg(revision)
+        .current_dir(dir)
+        .status()?;
+    wait(status)
+}
+
+/// commit id of the remote HEAD
+pub fn remote_head(url: &str) -> Result<String> {
+    let output = Command::new(GIT_BIN)
+        .arg("ls-remote")
+        .arg(url)
+        .arg("HEAD")
+        .output()?;
+    wait(output.status)?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .map(|commit_id| commit_id.to_string())
+        .ok_or(anyhow!("can't find HEAD of {}", url))
+}
+
+/// fetch a branch, tag or commit of the origin into `FETCH_HEAD`
+pub fn fetch<P: AsRef<Path>>(dir: P, revision: &str) -> Result<()> {
+    let status = Command::new(GIT_BIN)
+        .arg("fetch")
+        .arg("--quiet")
+        .arg("origin")
+        .arg(revision)
+        .current_dir(dir)
+        .status()?;
+    wait(status)
+}
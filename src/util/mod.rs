@@ -1,3 +1,7 @@
+pub mod changes;
 pub mod cli;
 pub mod docker;
+pub mod dwarf;
+pub mod elf;
+pub mod fs;
 pub mod git;
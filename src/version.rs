@@ -19,3 +19,59 @@ pub fn version_string() -> String {
     version.push_str(commit_id);
     version.trim().to_string()
 }
+
+/// (major, minor, patch) of a version string, e.g. `0.4.3 abcdefg`
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let number = version.split_whitespace().next()?;
+    // ignore pre-release
+    let number = number.split('-').next()?;
+    let mut parts = number.split('.').map(|n| n.parse::<u64>());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Some((major, minor, patch)),
+        _ => None,
+    }
+}
+
+/// projects are compatible with capsule if major and minor versions are the same,
+/// patch releases don't change the project layout
+pub fn is_compatible(project_version: &str) -> bool {
+    versions_compatible(project_version, &version_string())
+}
+
+fn versions_compatible(project_version: &str, capsule_version: &str) -> bool {
+    match (
+        parse_version(project_version),
+        parse_version(capsule_version),
+    ) {
+        (Some((major, minor, _)), Some((capsule_major, capsule_minor, _))) => {
+            major == capsule_major && minor == capsule_minor
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("0.4.3 abcdef"), Some((0, 4, 3)));
+        assert_eq!(parse_version("0.5.0-pre.1 abcdef"), Some((0, 5, 0)));
+        assert_eq!(parse_version("0.5.0"), Some((0, 5, 0)));
+        assert_eq!(parse_version("0.5"), None);
+        assert_eq!(parse_version("0.5.0.1"), None);
+        assert_eq!(parse_version("abcdef"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn test_versions_compatible() {
+        assert!(versions_compatible("0.5.1 abcdef", "0.5.0"));
+        assert!(versions_compatible("0.5.0", "0.5.3-pre abcdef"));
+        assert!(!versions_compatible("0.4.3 abcdef", "0.5.0"));
+        assert!(!versions_compatible("1.5.0", "0.5.0"));
+        assert!(!versions_compatible("unknown", "0.5.0"));
+        assert!(is_compatible(&version_string()));
+    }
+}
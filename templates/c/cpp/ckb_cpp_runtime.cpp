@@ -0,0 +1,14 @@
+/* Definitions of the freestanding C++ runtime, see ckb_cpp_runtime.h */
+#include "ckb_cpp_runtime.h"
+
+void operator delete(void *) noexcept {}
+void operator delete(void *, size_t) noexcept {}
+
+extern "C" {
+void *__dso_handle = 0;
+
+// destructors of static objects are never run
+int __cxa_atexit(void (*)(void *), void *, void *) { return 0; }
+
+void __cxa_pure_virtual() { ckb_exit(-1); }
+}
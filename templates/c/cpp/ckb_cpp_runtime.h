@@ -0,0 +1,33 @@
+/* Minimal freestanding C++ runtime for CKB scripts
+ *
+ * Scripts are built with -fno-exceptions -fno-rtti and without libstdc++,
+ * the symbols required by the compiler are defined in ckb_cpp_runtime.cpp,
+ * which is linked into every C++ contract.
+ * Notice: global constructors are not executed, avoid non-trivial globals.
+ */
+#ifndef CKB_CPP_RUNTIME_H_
+#define CKB_CPP_RUNTIME_H_
+
+#include <stddef.h>
+
+extern "C" {
+#include "ckb_syscalls.h"
+}
+
+// placement new, <new> is not available
+inline void *operator new(size_t, void *ptr) noexcept { return ptr; }
+inline void *operator new[](size_t, void *ptr) noexcept { return ptr; }
+
+// there is no heap, delete is only referenced by virtual destructors
+void operator delete(void *) noexcept;
+void operator delete(void *, size_t) noexcept;
+
+extern "C" {
+extern void *__dso_handle;
+
+int __cxa_atexit(void (*)(void *), void *, void *);
+
+void __cxa_pure_virtual();
+}
+
+#endif /* CKB_CPP_RUNTIME_H_ */
@@ -0,0 +1,69 @@
+#include "ckb_cpp_runtime.h"
+
+extern "C" {
+#include "blockchain.h"
+#include "stdio.h"
+}
+
+// Common error codes that might be returned by the script.
+// Notice: return non-zero value means the script varification is failed.
+enum Error : int {
+  ERROR_ARGUMENTS_LEN = -1,
+  ERROR_ENCODING = -2,
+  ERROR_SYSCALL = -3,
+  ERROR_SCRIPT_TOO_LONG = -21,
+  ERROR_MY_ERR = 5,
+};
+
+constexpr size_t SCRIPT_SIZE = 32768;
+
+// Load current script into the buffer
+class Script {
+ public:
+  int load() {
+    len_ = SCRIPT_SIZE;
+    int ret = ckb_load_script(data_, &len_, 0);
+    if (ret != CKB_SUCCESS) {
+      return ERROR_SYSCALL;
+    }
+    if (len_ > SCRIPT_SIZE) {
+      return ERROR_SCRIPT_TOO_LONG;
+    }
+    seg_.ptr = data_;
+    seg_.size = len_;
+    // Verify data is a valid molecule structure
+    if (MolReader_Script_verify(&seg_, false) != MOL_OK) {
+      return ERROR_ENCODING;
+    }
+    return CKB_SUCCESS;
+  }
+
+  mol_seg_t args() const {
+    mol_seg_t args_seg = MolReader_Script_get_args(&seg_);
+    return MolReader_Bytes_raw_bytes(&args_seg);
+  }
+
+ private:
+  uint8_t data_[SCRIPT_SIZE];
+  uint64_t len_ = 0;
+  mol_seg_t seg_;
+};
+
+int main() {
+  Script script;
+  int ret = script.load();
+  if (ret != CKB_SUCCESS) {
+    return ret;
+  }
+
+  mol_seg_t args_bytes_seg = script.args();
+  // the printf only compiled under debug build
+  printf("args length: %ld", args_bytes_seg.size);
+
+  // return an error is args is invalid
+  if (args_bytes_seg.size == 0) {
+    return ERROR_MY_ERR;
+  }
+
+  return CKB_SUCCESS;
+}
@@ -0,0 +1,36 @@
+//! Example of calling `{{name}}.so` from a contract
+//!
+//! The library must be deployed as a cell dep of the transaction,
+//! and the caller knows the data hash of the library cell.
+//! Copy the code into `entry.rs` of a contract,
+//! and add `LoadLibrary` and `UnexpectedResult` to its `Error`.
+
+use crate::error::Error;
+use ckb_std::dynamic_loading::{CKBDLContext, Symbol};
+
+// data hash of `{{name}}.so`
+const {{name | upper | replace(from="-", to="_")}}_DATA_HASH: [u8; 32] = [0u8; 32];
+
+type PlusFn = unsafe extern "C" fn(num: u32) -> u32;
+type ReadArgsLenFn = unsafe extern "C" fn(args_len: *mut u64) -> i32;
+
+pub fn main() -> Result<(), Error> {
+    // the context holds the loaded code, 128KB is enough for the example
+    let mut context = unsafe { CKBDLContext::<[u8; 128 * 1024]>::new() };
+    let lib = context
+        .load(&{{name | upper | replace(from="-", to="_")}}_DATA_HASH)
+        .map_err(|_| Error::LoadLibrary)?;
+
+    let plus_42: Symbol<PlusFn> = unsafe { lib.get(b"plus_42").ok_or(Error::LoadLibrary)? };
+    if unsafe { plus_42(13) } != 55 {
+        return Err(Error::UnexpectedResult);
+    }
+
+    let read_args_len: Symbol<ReadArgsLenFn> =
+        unsafe { lib.get(b"read_args_len").ok_or(Error::LoadLibrary)? };
+    let mut args_len = 0u64;
+    if unsafe { read_args_len(&mut args_len) } != 0 {
+        return Err(Error::UnexpectedResult);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Generated by capsule
+//!
+//! The library is built into `{{name}}.so` and loaded by `ckb_dlopen`,
+//! `#[no_mangle] pub extern "C"` functions are exported.
+//! See `caller_example.rs` for how to call it from a contract.
+
+#![no_std]
+#![feature(lang_items)]
+#![feature(alloc_error_handler)]
+
+use ckb_std::{ckb_types::prelude::*, default_alloc, high_level::load_script};
+use core::panic::PanicInfo;
+
+default_alloc!();
+
+// Common error codes that might be returned by the library.
+const ERROR_SYSCALL: i32 = -3;
+
+#[no_mangle]
+pub extern "C" fn plus_42(num: u32) -> u32 {
+    42 + num
+}
+
+/// load args length of the current script
+#[no_mangle]
+pub extern "C" fn read_args_len(args_len: *mut u64) -> i32 {
+    let script = match load_script() {
+        Ok(script) => script,
+        Err(_) => return ERROR_SYSCALL,
+    };
+    let len = script.args().raw_data().len();
+    // the printf only compiled under debug build
+    ckb_std::debug!("args length: {}", len);
+    unsafe {
+        *args_len = len as u64;
+    }
+    0
+}
+
+#[panic_handler]
+fn panic_handler(_info: &PanicInfo) -> ! {
+    ckb_std::syscalls::exit(-1)
+}
+
+#[lang = "eh_personality"]
+extern "C" fn eh_personality() {}
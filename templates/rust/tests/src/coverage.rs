@@ -0,0 +1,133 @@
+//! Code coverage of contracts, enabled by `capsule test --coverage`
+//!
+//! Script groups of a verified transaction are executed again by a VM which counts executed PCs.
+//! Counts are written into `$CAPSULE_COVERAGE_DIR/<contract>/*.trace`, one `<pc> <count>` per line,
+//! capsule maps them to source lines after tests.
+
+use super::Loader;
+use ckb_testtool::context::Context;
+use ckb_tool::ckb_script::{
+    cost_model::instruction_cycles, ScriptGroupType, TransactionScriptsVerifier,
+};
+use ckb_tool::ckb_types::{bytes::Bytes, core::TransactionView, packed::Byte32, prelude::*};
+use ckb_vm::{
+    decoder::build_decoder, memory::wxorx::WXorXMemory, CoreMachine, DefaultCoreMachine,
+    DefaultMachineBuilder, SparseMemory, SupportMachine,
+};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const COVERAGE_ENV_VAR: &str = "CAPSULE_COVERAGE_DIR";
+
+static TRACE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// name of the contract whose binary is the program, other scripts are not traced
+fn contract_name(program: &Bytes) -> Option<String> {
+    let Loader(dir) = Loader::default();
+    for entry in fs::read_dir(&dir).ok()? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if path.is_file() && fs::read(&path).ok().as_deref() == Some(&program[..]) {
+            return path.file_name()?.to_str().map(ToString::to_string);
+        }
+    }
+    None
+}
+
+/// record executed PCs of script groups of the tx, does nothing if coverage is disabled
+pub fn record(context: &Context, tx: &TransactionView, max_cycles: u64) {
+    let dir = match env::var(COVERAGE_ENV_VAR) {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => return,
+    };
+    let rtx = context.build_resolved_tx(tx);
+    let verifier = TransactionScriptsVerifier::new(&rtx, context);
+    let mut groups: Vec<(ScriptGroupType, Byte32)> = Vec::new();
+    for cell in &rtx.resolved_inputs {
+        let output = &cell.cell_output;
+        groups.push((ScriptGroupType::Lock, output.lock().calc_script_hash()));
+        if let Some(script) = output.type_().to_opt() {
+            groups.push((ScriptGroupType::Type, script.calc_script_hash()));
+        }
+    }
+    for output in tx.outputs().into_iter() {
+        if let Some(script) = output.type_().to_opt() {
+            groups.push((ScriptGroupType::Type, script.calc_script_hash()));
+        }
+    }
+    let mut recorded = Vec::new();
+    for group in groups {
+        if recorded.contains(&group) {
+            continue;
+        }
+        if let Err(err) = record_group(&verifier, group.0, &group.1, max_cycles, &dir) {
+            eprintln!("failed to record coverage of script {}: {}", group.1, err);
+        }
+        recorded.push(group);
+    }
+}
+
+fn record_group(
+    verifier: &TransactionScriptsVerifier<Context>,
+    group_type: ScriptGroupType,
+    script_hash: &Byte32,
+    max_cycles: u64,
+    dir: &Path,
+) -> Result<(), String> {
+    let group = verifier
+        .find_script_group(group_type, script_hash)
+        .ok_or_else(|| "can't find the script group".to_string())?;
+    let program = verifier
+        .extract_script(&group.script)
+        .map_err(|err| err.to_string())?;
+    let name = match contract_name(&program) {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+
+    let core_machine =
+        DefaultCoreMachine::<u64, WXorXMemory<u64, SparseMemory<u64>>>::new_with_max_cycles(
+            max_cycles,
+        );
+    let mut builder = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(instruction_cycles));
+    for syscall in verifier.generate_syscalls(group) {
+        builder = builder.syscall(syscall);
+    }
+    let mut machine = builder.build();
+    machine
+        .load_program(&program, &[])
+        .map_err(|err| format!("{:?}", err))?;
+    let mut decoder = build_decoder::<u64>();
+    let mut hits: HashMap<u64, u64> = HashMap::new();
+    let mut result = Ok(());
+    machine.set_running(true);
+    while machine.running() {
+        *hits.entry(*machine.pc()).or_insert(0) += 1;
+        if let Err(err) = machine.step(&mut decoder) {
+            // PCs executed before the error are still recorded
+            result = Err(format!("{:?}", err));
+            break;
+        }
+    }
+
+    let dir = dir.join(&name);
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let path = dir.join(format!(
+        "{}-{}.trace",
+        process::id(),
+        TRACE_COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let content: String = hits
+        .iter()
+        .map(|(pc, count)| format!("{:x} {}\n", pc, count))
+        .collect();
+    fs::write(path, content).map_err(|err| err.to_string())?;
+    result
+}
@@ -0,0 +1,201 @@
+use super::*;
+use ckb_testtool::context::Context;
+use ckb_tool::ckb_hash::blake2b_256;
+use ckb_tool::ckb_jsonrpc_types as json_types;
+use ckb_tool::ckb_types::{
+    bytes::Bytes,
+    core::{TransactionBuilder, TransactionView},
+    packed::*,
+    prelude::*,
+};
+use ckb_tool::faster_hex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::json;
+
+const CONTRACT: &str = "{{ name }}";
+const CONTRACT_DATA_MARK: &str = "{{ '{{' ~ name ~ '.data}}' }}";
+const CONTRACT_CODE_HASH_MARK: &str = "{{ '{{' ~ name ~ '.code_hash}}' }}";
+const CRASHES_DIR: &str = "../fuzz/{{ name }}/crashes";
+const MAX_CYCLES: u64 = 70_000_000;
+const MAX_CELLS: usize = 4;
+const MAX_BYTES: usize = 256;
+
+// fuzzing options
+const FUZZ_ITERATIONS_VAR: &str = "CAPSULE_FUZZ_ITERATIONS";
+const FUZZ_SEED_VAR: &str = "CAPSULE_FUZZ_SEED";
+const DEFAULT_ITERATIONS: u64 = 1000;
+
+fn env_u64(key: &str) -> Option<u64> {
+    env::var(key).ok().map(|val| val.parse().expect(key))
+}
+
+fn random_bytes(rng: &mut StdRng) -> Bytes {
+    let len = rng.gen_range(0, MAX_BYTES);
+    (0..len).map(|_| rng.gen::<u8>()).collect::<Vec<u8>>().into()
+}
+
+/// cell created for the transaction, kept to build the mock info
+struct MockCell {
+    out_point: OutPoint,
+    output: CellOutput,
+    data: Bytes,
+}
+
+/// build a random transaction which uses the contract as lock
+fn build_tx(
+    context: &mut Context,
+    rng: &mut StdRng,
+    contract_bin: &Bytes,
+) -> (TransactionView, Vec<MockCell>, MockCell) {
+    let out_point = context.deploy_cell(contract_bin.clone());
+    let (dep_output, dep_data) = context.get_cell(&out_point).expect("contract cell");
+    let contract_dep = MockCell {
+        out_point: out_point.clone(),
+        output: dep_output,
+        data: dep_data,
+    };
+
+    let mut inputs = Vec::new();
+    for _ in 0..rng.gen_range(1, MAX_CELLS) {
+        let lock_script = context
+            .build_script(&out_point, random_bytes(rng))
+            .expect("script");
+        let output = CellOutput::new_builder()
+            .capacity(rng.gen_range(1_000u64, 1_000_000u64).pack())
+            .lock(lock_script)
+            .build();
+        let data = random_bytes(rng);
+        let input_out_point = context.create_cell(output.clone(), data.clone());
+        inputs.push(MockCell {
+            out_point: input_out_point,
+            output,
+            data,
+        });
+    }
+
+    let mut outputs = Vec::new();
+    let mut outputs_data = Vec::new();
+    for _ in 0..rng.gen_range(0, MAX_CELLS) {
+        let lock_script = context
+            .build_script(&out_point, random_bytes(rng))
+            .expect("script");
+        outputs.push(
+            CellOutput::new_builder()
+                .capacity(rng.gen_range(0u64, 1_000_000u64).pack())
+                .lock(lock_script)
+                .build(),
+        );
+        outputs_data.push(random_bytes(rng));
+    }
+
+    let witnesses: Vec<Bytes> = (0..rng.gen_range(0, MAX_CELLS))
+        .map(|_| random_bytes(rng))
+        .collect();
+
+    let tx = TransactionBuilder::default()
+        .inputs(inputs.iter().map(|cell| {
+            CellInput::new_builder()
+                .previous_output(cell.out_point.clone())
+                .build()
+        }))
+        .outputs(outputs)
+        .outputs_data(outputs_data.pack())
+        .witnesses(witnesses.pack())
+        .cell_dep(CellDep::new_builder().out_point(out_point).build())
+        .build();
+    (tx, inputs, contract_dep)
+}
+
+/// script errors are expected, VM faults, cycles exhaustion and panics are crashes
+fn is_crash(err: &str) -> bool {
+    !err.contains("ValidationFailure") || err.contains("ValidationFailure(-1)")
+}
+
+/// save the transaction as a debugger template, see `capsule debugger start`
+fn save_crash(
+    tx: &TransactionView,
+    inputs: &[MockCell],
+    contract_dep: &MockCell,
+    contract_bin: &Bytes,
+    name: &str,
+) -> String {
+    let mock_inputs: Vec<_> = inputs
+        .iter()
+        .map(|cell| {
+            json!({
+                "input": json_types::CellInput::from(
+                    CellInput::new_builder()
+                        .previous_output(cell.out_point.clone())
+                        .build()
+                ),
+                "output": json_types::CellOutput::from(cell.output.clone()),
+                "data": json_types::JsonBytes::from_bytes(cell.data.clone()),
+                "header": null,
+            })
+        })
+        .collect();
+    let mock_cell_deps: Vec<_> = tx
+        .cell_deps()
+        .into_iter()
+        .map(|cell_dep| {
+            json!({
+                "cell_dep": json_types::CellDep::from(cell_dep),
+                "output": json_types::CellOutput::from(contract_dep.output.clone()),
+                "data": json_types::JsonBytes::from_bytes(contract_dep.data.clone()),
+                "header": null,
+            })
+        })
+        .collect();
+    let template = json!({
+        "mock_info": {
+            "inputs": mock_inputs,
+            "cell_deps": mock_cell_deps,
+            "header_deps": [],
+        },
+        "tx": json_types::Transaction::from(tx.data()),
+    });
+    // replace the contract with template marks so the template can be reused after rebuilding
+    let data_hex = format!("0x{}", faster_hex::hex_string(contract_bin).expect("hex"));
+    let code_hash_hex = format!(
+        "0x{}",
+        faster_hex::hex_string(&blake2b_256(contract_bin)).expect("hex")
+    );
+    let content = serde_json::to_string_pretty(&template)
+        .expect("json")
+        .replace(&data_hex, CONTRACT_DATA_MARK)
+        .replace(&code_hash_hex, CONTRACT_CODE_HASH_MARK);
+    fs::create_dir_all(CRASHES_DIR).expect("create crashes dir");
+    let path = format!("{}/{}.json", CRASHES_DIR, name);
+    fs::write(&path, content).expect("write crash");
+    path
+}
+
+#[test]
+#[ignore]
+fn fuzz() {
+    let iterations = env_u64(FUZZ_ITERATIONS_VAR).unwrap_or(DEFAULT_ITERATIONS);
+    let seed = env_u64(FUZZ_SEED_VAR).unwrap_or_else(rand::random);
+    println!(
+        "fuzzing {} with seed {}, {} iterations",
+        CONTRACT, seed, iterations
+    );
+    let contract_bin: Bytes = Loader::default().load_binary(CONTRACT);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut crashes = Vec::new();
+    for i in 0..iterations {
+        let mut context = Context::default();
+        let (tx, inputs, contract_dep) = build_tx(&mut context, &mut rng, &contract_bin);
+        let tx = context.complete_tx(tx);
+        let err = match context.verify_tx(&tx, MAX_CYCLES) {
+            Ok(_) => continue,
+            Err(err) => format!("{:?}", err),
+        };
+        if is_crash(&err) {
+            let name = format!("{}-{}", seed, i);
+            let path = save_crash(&tx, &inputs, &contract_dep, &contract_bin, &name);
+            println!("crash at iteration {}: {}\nsaved to {}", i, err, path);
+            crashes.push(path);
+        }
+    }
+    assert!(crashes.is_empty(), "found {} crashes", crashes.len());
+}
@@ -1,9 +1,17 @@
-use ckb_tool::ckb_types::bytes::Bytes;
+use ckb_testtool::context::Context;
+use ckb_tool::ckb_error::Error;
+use ckb_tool::ckb_types::{
+    bytes::Bytes,
+    core::{Cycle, TransactionView},
+    packed::{Byte32, CellDep, CellOutput},
+    prelude::*,
+};
 use std::env;
 use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+pub mod coverage;
 #[cfg(test)]
 mod tests;
 
@@ -53,9 +61,44 @@ impl Loader {
         Loader(base_path)
     }
 
-    pub fn load_binary(&self, name: &str) -> Bytes {
+    pub fn try_load_binary(&self, name: &str) -> Result<Bytes, String> {
         let mut path = self.0.clone();
         path.push(name);
-        fs::read(path).expect("binary").into()
+        let release_opt = if self.0.ends_with("release") {
+            " --release"
+        } else {
+            ""
+        };
+        fs::read(&path).map(Into::into).map_err(|err| {
+            format!(
+                "failed to load contract '{}' from {:?}: {}\nhint: run `capsule build{} -n {}` first",
+                name, path, err, release_opt, name
+            )
+        })
+    }
+
+    pub fn load_binary(&self, name: &str) -> Bytes {
+        self.try_load_binary(name).unwrap_or_else(|err| panic!("{}", err))
     }
 }
+
+/// Deploy a shared library as cell dep, e.g. `deploy_shared_lib(&mut context, "mylib")`
+/// loads `mylib.so` from the build dir.
+/// Returns the cell dep and the data hash which is used to load the library by `ckb_dlopen`.
+pub fn deploy_shared_lib(context: &mut Context, name: &str) -> (CellDep, Byte32) {
+    let lib = Loader::default().load_binary(&format!("{}.so", name));
+    let data_hash = CellOutput::calc_data_hash(&lib);
+    let out_point = context.deploy_cell(lib);
+    let cell_dep = CellDep::new_builder().out_point(out_point).build();
+    (cell_dep, data_hash)
+}
+
+/// Verify the tx, executed PCs of contracts are also recorded by `capsule test --coverage`.
+pub fn verify_tx(
+    context: &Context,
+    tx: &TransactionView,
+    max_cycles: Cycle,
+) -> Result<Cycle, Error> {
+    coverage::record(context, tx, max_cycles);
+    context.verify_tx(tx, max_cycles)
+}
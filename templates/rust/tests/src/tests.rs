@@ -63,8 +63,7 @@ fn test_success() {
     let tx = context.complete_tx(tx);
 
     // run
-    let cycles = context
-        .verify_tx(&tx, MAX_CYCLES)
+    let cycles = verify_tx(&context, &tx, MAX_CYCLES)
         .expect("pass verification");
     println!("consume cycles: {}", cycles);
 }
@@ -118,8 +117,7 @@ fn test_empty_args() {
     let tx = context.complete_tx(tx);
 
     // run
-    let err = context
-        .verify_tx(&tx, MAX_CYCLES)
+    let err = verify_tx(&context, &tx, MAX_CYCLES)
         .unwrap_err();
     // we expect an error raised from 0-indexed cell's lock script
     let script_cell_index = 0;
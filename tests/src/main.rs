@@ -27,6 +27,9 @@ fn main() {
     test_build(&tmp_dir, &bin_path, "c-demo", "c").expect("c demo");
     test_build_sharedlib(&tmp_dir, &bin_path, "c-sharedlib-demo", "c-sharedlib")
         .expect("c sharedlib demo");
+    test_build(&tmp_dir, &bin_path, "cpp-demo", "cpp").expect("cpp demo");
+    test_build_sharedlib(&tmp_dir, &bin_path, "rust-sharedlib-demo", "rust-sharedlib")
+        .expect("rust sharedlib demo");
 
     // clean
     fs::remove_dir_all(&tmp_dir).expect("remove dir");